@@ -1,10 +1,14 @@
-use crate::utils::{calc_board_size_stretched, is_quit_key, map_range};
+use crate::gamepad;
+use crate::input::{self, Action};
+use crate::utils::{
+    calc_board_size_stretched, draw_palette_swatch, map_range, next_seed, value_enum_name,
+    wrap_coord, Backoff, RunOptions, Topology,
+};
 use color_eyre::Result;
-use crossterm::event::KeyEventKind;
 use glam::DVec2;
 use oorandom::Rand64;
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode},
+    crossterm::event::{self, Event},
     style::Color,
     symbols::Marker,
     widgets::{
@@ -15,21 +19,53 @@ use ratatui::{
 };
 use std::time::{Duration, Instant};
 
+/// (active, color index)
+pub type CellState = (bool, u8);
+
+/// steps for each heading, clockwise from up. The classic ant only uses the even ones
+const DIRECTIONS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
 pub struct App {
-    grid: Vec<Vec<(bool, u8)>>,
+    grid: Vec<Vec<CellState>>,
     exit: bool,
     playground: DVec2,
     debug_text: String,
+    quiet: bool,
+    // c shows the colors in use
+    show_palette: bool,
     marker: Marker,
     pixel: bool,
+    // (index into DIRECTIONS, x, y)
     ant: (u8, usize, usize),
+    // turn by 45 degrees instead of 90 and move diagonally too
+    diagonal: bool,
     rng: Rand64,
     speed: usize,
     n_colors: u8,
     dist_by_color: bool,
     filled: bool,
+    edges: Topology,
     pattern: Option<usize>,
     pattern_len: Option<usize>,
+    no_reset: bool,
+    // set once the board is saturated with no_reset
+    frozen: bool,
+    // recenter the world on the ant instead of resetting
+    scroll: bool,
+    // ratio of touched cells that counts as saturated
+    reset_threshold: f32,
+    loop_seed: bool,
+    tick_rate: u64,
+    seed: u128,
 }
 
 impl App {
@@ -40,24 +76,32 @@ impl App {
         marker: Marker,
         speed: usize,
         board_width: Option<usize>,
+        board_height: Option<usize>,
         n_colors: u8,
         dist_by_color: bool,
         filled: bool,
+        edges: Topology,
         pattern: Option<u8>,
         pattern_len: Option<usize>,
         seed: u128,
+        no_reset: bool,
+        scroll: bool,
+        reset_threshold: f32,
+        diagonal: bool,
     ) -> Self {
         let scale_factor = terminal_height as f32 / terminal_width as f32;
         let font_scale_factor = 2.0;
         let width = 200.0;
         let height = width * scale_factor * font_scale_factor;
         let wh_factor = height / width;
-        let pixel = board_width.is_none();
+        let pixel = board_width.is_none() && board_height.is_none();
         let mut grid = Vec::new();
 
-        let (board_width, board_height) = match board_width {
-            Some(width) => (width, (width as f32 * wh_factor) as usize),
-            None => calc_board_size_stretched(marker, terminal_width, terminal_height),
+        let (board_width, board_height) = match (board_width, board_height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, ((width as f32 * wh_factor) as usize).max(1)),
+            (None, Some(height)) => (((height as f32 / wh_factor) as usize).max(1), height),
+            (None, None) => calc_board_size_stretched(marker, terminal_width, terminal_height),
         };
 
         for _ in 0..board_height {
@@ -74,55 +118,170 @@ impl App {
             debug_text: String::new(),
             pixel,
             ant: (0, board_width / 2, board_height / 2),
+            diagonal,
             rng: oorandom::Rand64::new(seed),
             speed,
             n_colors,
             dist_by_color,
             filled,
+            edges,
             pattern: pattern.map(|n| n as usize),
             pattern_len,
+            no_reset,
+            frozen: false,
+            scroll,
+            reset_threshold,
+            loop_seed: false,
+            quiet: false,
+            show_palette: false,
+            tick_rate: 0,
+            seed,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        let tick_rate = Duration::from_millis(16);
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        self.loop_seed = options.loop_seed;
+        let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
         self.reset();
 
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
+                    Event::Key(key) if !options.skip_turn(key) => {
+                        if let Some(action) = input::map_key(key) {
+                            self.apply(action);
+                        }
+                    }
                     Event::Resize(_columns, _rows) => {
                         // self.debug_text = format!("{} {}", columns, rows);
                     }
                     _ => (),
                 }
             }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
 
             if last_tick.elapsed() >= tick_rate {
-                for _ in 0..self.speed {
-                    self.on_tick();
+                if !self.frozen {
+                    for _ in 0..self.speed {
+                        self.on_tick();
+                    }
                 }
-                let board_height = self.grid.len();
-                let board_width = self.grid[0].len();
+                let (board_width, board_height) = self.dimensions();
                 let total = board_height * board_width;
                 let n_touched = self.grid.iter().flatten().filter(|p| p.1 > 1).count();
-                let n_active = self.grid.iter().flatten().filter(|p| p.0).count();
+                let n_active = self.live_count();
                 let percentage_touched = n_touched as f32 / total as f32;
                 let percentage_active = n_active as f32 / total as f32;
-                if percentage_touched > 0.9 && percentage_active > 0.4 {
-                    self.reset();
+                if !self.scroll
+                    && percentage_touched > self.reset_threshold
+                    && percentage_active > 0.4
+                {
+                    if self.no_reset {
+                        self.frozen = true;
+                    } else {
+                        self.auto_reset();
+                    }
                 }
                 last_tick = Instant::now();
             }
         }
-        Ok(())
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "ant --marker {} --tick-rate {} --speed {} --n-colors {} --edges {} --seed {} --reset-threshold {}",
+            self.marker,
+            self.tick_rate,
+            self.speed,
+            self.n_colors,
+            value_enum_name(self.edges),
+            self.seed,
+            self.reset_threshold
+        );
+        if !self.pixel {
+            let (width, height) = self.dimensions();
+            args += &format!(" --width {width} --height {height}");
+        }
+        if self.dist_by_color {
+            args += " --dist-by-color";
+        }
+        if self.filled {
+            args += " --filled";
+        }
+        if let Some(pattern) = self.pattern {
+            args += &format!(" --pattern {pattern}");
+        }
+        if let Some(pattern_len) = self.pattern_len {
+            args += &format!(" --pattern-len {pattern_len}");
+        }
+        if self.no_reset {
+            args += " --no-reset";
+        }
+        if self.scroll {
+            args += " --scroll";
+        }
+        if self.diagonal {
+            args += " --diagonal";
+        }
+        args
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> Option<CellState> {
+        self.grid.get(y).and_then(|line| line.get(x)).copied()
+    }
+
+    /// (width, height) of the board in cells
+    pub fn dimensions(&self) -> (usize, usize) {
+        (
+            self.grid.first().map_or(0, |line| line.len()),
+            self.grid.len(),
+        )
+    }
+
+    /// number of active cells
+    pub fn live_count(&self) -> usize {
+        self.grid.iter().flatten().filter(|c| c.0).count()
+    }
+
+    /// reset on its own, starting from a new seed with --loop-seed
+    fn auto_reset(&mut self) {
+        if self.loop_seed {
+            self.seed = next_seed(&mut self.rng);
+            self.rng = oorandom::Rand64::new(self.seed);
+        }
+        self.reset();
+    }
+
+    /// start over from `seed`, n and N step through seeds
+    fn set_seed(&mut self, seed: u128) {
+        self.seed = seed;
+        self.rng = oorandom::Rand64::new(seed);
+        self.reset();
+        self.debug_text = format!("seed: {seed}");
     }
 
     fn reset(&mut self) {
+        self.frozen = false;
         let height = self.grid.len();
         let width = self.grid[0].len();
         let pattern = match self.pattern {
@@ -152,17 +311,14 @@ impl App {
         }
     }
 
-    fn handle_key_press(&mut self, key: event::KeyEvent) {
-        if key.kind != KeyEventKind::Press {
-            return;
-        }
-        match key.code {
-            KeyCode::Char('r') => self.reset(),
-            _ => {
-                if is_quit_key(key) {
-                    self.exit = true;
-                }
-            }
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Reset => self.reset(),
+            Action::NextSeed => self.set_seed(self.seed.wrapping_add(1)),
+            Action::PrevSeed => self.set_seed(self.seed.wrapping_sub(1)),
+            Action::TogglePalette => self.show_palette = !self.show_palette,
+            Action::Quit => self.exit = true,
+            _ => (),
         }
     }
 
@@ -178,13 +334,16 @@ impl App {
         self.grid[y][x].0 = !self.grid[y][x].0;
 
         let current_color = self.grid[y][x].1 as u16;
+        // a right or left turn, in steps of DIRECTIONS
+        let turn = if self.diagonal { 1 } else { 2 };
 
         if square_is_black {
-            dir = (dir + 1) % 4;
-            let new_color = (current_color + 1).clamp(1, self.n_colors as u16 - 1) as u8;
+            dir = (dir + turn) % 8;
+            let max_color = (self.n_colors as u16).saturating_sub(1).max(1);
+            let new_color = (current_color + 1).clamp(1, max_color) as u8;
             self.grid[y][x].1 = new_color;
         } else {
-            dir = (dir as i32 - 1).rem_euclid(4) as u8;
+            dir = (dir + 8 - turn) % 8;
             if self.grid[y][x].1 == 0 {
                 self.grid[y][x].1 = 1;
             }
@@ -196,19 +355,63 @@ impl App {
             1
         };
 
-        match dir {
-            0 => y = (y + dist) % board_height,
-            1 => x = (x + dist) % board_width,
-            2 => y = (y as i32 - dist as i32).rem_euclid(board_height as i32) as usize,
-            3 => x = (x as i32 - dist as i32).rem_euclid(board_width as i32) as usize,
-            _ => unreachable!(),
+        let dist = dist as i32;
+        let (x_off, y_off) = DIRECTIONS[dir as usize];
+        let (x_off, y_off) = (x_off * dist, y_off * dist);
+        if self.scroll {
+            let margin = (board_width.min(board_height) / 8).max(1) as i32;
+            let (new_x, new_y) = (x as i32 + x_off, y as i32 + y_off);
+            if new_x < margin
+                || new_y < margin
+                || new_x >= board_width as i32 - margin
+                || new_y >= board_height as i32 - margin
+            {
+                (x, y) = self.recenter(x, y);
+            }
+        }
+        // on a bounded board the ant stays put and turns again next tick
+        if let (Some(new_x), Some(new_y)) = (
+            wrap_coord(x as i32 + x_off, board_width as i32, self.edges),
+            wrap_coord(y as i32 + y_off, board_height as i32, self.edges),
+        ) {
+            x = new_x;
+            y = new_y;
         }
         self.ant = (dir, x, y);
     }
 
+    /// shift the world so the ant at (x, y) is back in the middle,
+    /// dropping whatever leaves the board. returns the new ant position
+    fn recenter(&mut self, x: usize, y: usize) -> (usize, usize) {
+        let (width, height) = self.dimensions();
+        let (center_x, center_y) = (width / 2, height / 2);
+        let dx = center_x as i64 - x as i64;
+        let dy = center_y as i64 - y as i64;
+        let mut grid = vec![vec![(false, 0); width]; height];
+        for (old_y, line) in self.grid.iter().enumerate() {
+            for (old_x, &cell) in line.iter().enumerate() {
+                let new_x = old_x as i64 + dx;
+                let new_y = old_y as i64 + dy;
+                if (0..width as i64).contains(&new_x) && (0..height as i64).contains(&new_y) {
+                    grid[new_y as usize][new_x as usize] = cell;
+                }
+            }
+        }
+        self.grid = grid;
+        (center_x, center_y)
+    }
+
     fn draw(&self, frame: &mut Frame) {
         frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+        if self.show_palette && !self.quiet {
+            let area = frame.area();
+            draw_palette_swatch(
+                frame.buffer_mut(),
+                area,
+                1..=self.n_colors.saturating_sub(1).max(1),
+            );
+        }
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
@@ -1,6 +1,10 @@
-use crate::utils::is_quit_key;
+use crate::utils::{is_quit_key, Backoff, RunOptions};
 use color_eyre::Result;
-use crossterm::event::KeyEventKind;
+use crossterm::event::{
+    DisableMouseCapture, EnableMouseCapture, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::execute;
+use oorandom::Rand64;
 use ratatui::{
     crossterm::event::{self, Event},
     layout::Rect,
@@ -12,25 +16,47 @@ use ratatui::{
     },
     DefaultTerminal, Frame,
 };
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+// push of the mouse at distance 1, falls off with the distance
+const MOUSE_FORCE: f64 = 8.0;
+// so balls right under the cursor don't shoot off
+const MAX_MOUSE_ACCELERATION: f64 = 1.5;
+
 struct Ball {
     circle: Circle,
     vx: f64,
     vy: f64,
+    // previous positions, oldest first
+    history: VecDeque<(f64, f64)>,
+}
+
+/// random kick of up to `amount` either way, draws nothing from `rng` when 0
+fn jitter(rng: &mut Rand64, amount: f64) -> f64 {
+    if amount > 0.0 {
+        (rng.rand_float() * 2.0 - 1.0) * amount
+    } else {
+        0.0
+    }
 }
 
 impl Ball {
     fn new(vx: f64, vy: f64) -> Self {
+        Self::new_at(20.0, 40.0, vx, vy)
+    }
+
+    fn new_at(x: f64, y: f64, vx: f64, vy: f64) -> Self {
         Self {
             circle: Circle {
-                x: 20.0,
-                y: 40.0,
+                x,
+                y,
                 radius: 5.0,
                 color: Color::Yellow,
             },
             vx,
             vy,
+            history: VecDeque::new(),
         }
     }
 }
@@ -42,12 +68,34 @@ pub struct App {
     tick_count: u64,
     ball_count: u16,
     debug_text: String,
+    quiet: bool,
     marker: Marker,
     max_balls: u16,
+    ceiling: bool,
+    wrap: bool,
+    trail: Option<usize>,
+    rng: Rand64,
+    seed: u128,
+    jitter: f64,
+    terminal_size: (u16, u16),
+    // (x, y, strength) while a mouse button is held, positive pushes balls away
+    mouse_force: Option<(f64, f64, f64)>,
+    tick_rate: u64,
 }
 
 impl App {
-    pub fn new(terminal_width: u16, terminal_height: u16, marker: Marker, max_balls: u16) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        terminal_width: u16,
+        terminal_height: u16,
+        marker: Marker,
+        max_balls: u16,
+        ceiling: bool,
+        wrap: bool,
+        trail: Option<usize>,
+        seed: u128,
+        jitter: f64,
+    ) -> Self {
         let scale_factor = terminal_height as f32 / terminal_width as f32;
         let font_scale_factor = 2.0;
         let width = 200.0;
@@ -62,21 +110,44 @@ impl App {
             marker,
             debug_text: String::new(),
             max_balls,
+            ceiling,
+            wrap,
+            trail,
+            rng: oorandom::Rand64::new(seed),
+            seed,
+            jitter,
+            terminal_size: (terminal_width, terminal_height),
+            mouse_force: None,
+            quiet: false,
+            tick_rate: 0,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        let tick_rate = Duration::from_millis(16);
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
-        let mut rng = oorandom::Rand64::new(99);
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        let mut backoff = Backoff::new(options.adaptive);
+        execute!(std::io::stdout(), EnableMouseCapture)?;
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
-                    Event::Resize(_columns, _rows) => {
-                        // self.debug_text = format!("{} {}", columns, rows);
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
+                    Event::Mouse(mouse) => self.handle_mouse(mouse),
+                    Event::Resize(columns, rows) => {
+                        self.terminal_size = (columns, rows);
                     }
                     _ => (),
                 }
@@ -86,15 +157,34 @@ impl App {
                 self.on_tick();
                 last_tick = Instant::now();
                 if self.tick_count.is_multiple_of(20) && self.ball_count < self.max_balls {
-                    let x = 1.0 + 3.0 * rng.rand_float();
-                    let y = 1.0 + 3.0 * rng.rand_float();
+                    let x = 1.0 + 3.0 * self.rng.rand_float();
+                    let y = 1.0 + 3.0 * self.rng.rand_float();
                     self.ball_count += 1;
                     // self.debug_text = format!("{}", self.ball_count);
                     self.balls.push(Ball::new(x, y));
                 }
             }
         }
-        Ok(())
+        execute!(std::io::stdout(), DisableMouseCapture)?;
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "balls --marker {} --tick-rate {} --max-balls {} --seed {} --jitter {}",
+            self.marker, self.tick_rate, self.max_balls, self.seed, self.jitter
+        );
+        if self.ceiling {
+            args += " --ceiling";
+        }
+        if self.wrap {
+            args += " --wrap";
+        }
+        if let Some(trail) = self.trail {
+            args += &format!(" --trail {trail}");
+        }
+        args
     }
 
     fn handle_key_press(&mut self, key: event::KeyEvent) {
@@ -106,18 +196,79 @@ impl App {
         }
     }
 
+    /// left button pushes balls away from the cursor, right pulls them in,
+    /// middle drops a new ball
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let (columns, rows) = self.terminal_size;
+        if columns == 0 || rows == 0 {
+            return;
+        }
+        // canvas y goes up, terminal rows go down
+        let x = (mouse.column as f64 + 0.5) * self.playground.width as f64 / columns as f64;
+        let y =
+            (rows as f64 - mouse.row as f64 - 0.5) * self.playground.height as f64 / rows as f64;
+        self.mouse_force =
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left)
+                | MouseEventKind::Drag(MouseButton::Left) => Some((x, y, MOUSE_FORCE)),
+                MouseEventKind::Down(MouseButton::Right)
+                | MouseEventKind::Drag(MouseButton::Right) => Some((x, y, -MOUSE_FORCE)),
+                MouseEventKind::Down(MouseButton::Middle) => {
+                    self.balls.push(Ball::new_at(x, y, 0.0, 0.0));
+                    None
+                }
+                MouseEventKind::Up(_) => None,
+                _ => self.mouse_force,
+            };
+    }
+
     fn on_tick(&mut self) {
         self.tick_count += 1;
         for ball in self.balls.iter_mut() {
-            let playground = self.playground;
-            if ball.circle.x - ball.circle.radius < f64::from(playground.left())
-                || ball.circle.x + ball.circle.radius > f64::from(playground.right())
-            {
-                ball.vx = -ball.vx;
+            if let Some((x, y, strength)) = self.mouse_force {
+                let (dx, dy) = (ball.circle.x - x, ball.circle.y - y);
+                let distance = dx.hypot(dy).max(1.0);
+                let acceleration =
+                    (strength / distance).clamp(-MAX_MOUSE_ACCELERATION, MAX_MOUSE_ACCELERATION);
+                ball.vx += acceleration * dx / distance;
+                ball.vy += acceleration * dy / distance;
             }
-            // no top barrier
-            if ball.circle.y - ball.circle.radius < f64::from(playground.top()) {
-                ball.vy = -ball.vy;
+            if let Some(trail) = self.trail {
+                ball.history.push_back((ball.circle.x, ball.circle.y));
+                while ball.history.len() > trail {
+                    ball.history.pop_front();
+                }
+            }
+            let playground = self.playground;
+            if self.wrap {
+                // leaving one edge comes back in at the opposite one
+                let (width, height) = (f64::from(playground.width), f64::from(playground.height));
+                if ball.circle.x < f64::from(playground.left()) {
+                    ball.circle.x += width;
+                } else if ball.circle.x > f64::from(playground.right()) {
+                    ball.circle.x -= width;
+                }
+                if ball.circle.y < f64::from(playground.top()) {
+                    ball.circle.y += height;
+                } else if ball.circle.y > f64::from(playground.bottom()) {
+                    ball.circle.y -= height;
+                }
+            } else {
+                if ball.circle.x - ball.circle.radius < f64::from(playground.left())
+                    || ball.circle.x + ball.circle.radius > f64::from(playground.right())
+                {
+                    ball.vx = -ball.vx + jitter(&mut self.rng, self.jitter);
+                }
+                // no top barrier
+                if ball.circle.y - ball.circle.radius < f64::from(playground.top()) {
+                    ball.vy = -ball.vy + jitter(&mut self.rng, self.jitter);
+                }
+                // canvas y points up, so the top of the screen is playground.bottom()
+                if self.ceiling
+                    && ball.circle.y + ball.circle.radius > f64::from(playground.bottom())
+                {
+                    ball.vy = -ball.vy.abs() + jitter(&mut self.rng, self.jitter);
+                }
             }
 
             ball.circle.x += ball.vx;
@@ -132,7 +283,7 @@ impl App {
 
     fn draw(&self, frame: &mut Frame) {
         frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
@@ -142,6 +293,19 @@ impl App {
         Canvas::default()
             .marker(self.marker)
             .paint(|ctx| {
+                for ball in self.balls.iter() {
+                    let n = ball.history.len();
+                    for (i, &(x, y)) in ball.history.iter().enumerate() {
+                        // older positions are smaller and darker, grayscale ramp 232..=255
+                        let age = (n - i) as f64 / (n + 1) as f64;
+                        ctx.draw(&Circle {
+                            x,
+                            y,
+                            radius: ball.circle.radius * (1.0 - age),
+                            color: Color::Indexed(255 - (23.0 * age) as u8),
+                        });
+                    }
+                }
                 for ball in self.balls.iter() {
                     ctx.draw(&ball.circle);
                 }
@@ -0,0 +1,273 @@
+use crate::gamepad;
+use crate::input::{self, Action};
+use crate::utils::{Backoff, RunOptions};
+use color_eyre::Result;
+use glam::DVec2;
+use oorandom::Rand64;
+use ratatui::{
+    crossterm::event::{self, Event},
+    layout::Rect,
+    style::Color,
+    symbols::Marker,
+    widgets::{
+        canvas::{Canvas, Line},
+        Paragraph, Widget,
+    },
+    DefaultTerminal, Frame,
+};
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+
+const VIEW_RADIUS: f64 = 15.0;
+const SEPARATION_RADIUS: f64 = 5.0;
+const MIN_SPEED: f64 = 0.8;
+const MAX_SPEED: f64 = 2.0;
+
+struct Boid {
+    position: DVec2,
+    velocity: DVec2,
+}
+
+/// shortest offset from a to b on a torus
+fn wrapped_offset(a: DVec2, b: DVec2, size: DVec2) -> DVec2 {
+    let mut d = b - a;
+    if d.x > size.x * 0.5 {
+        d.x -= size.x;
+    } else if d.x < -size.x * 0.5 {
+        d.x += size.x;
+    }
+    if d.y > size.y * 0.5 {
+        d.y -= size.y;
+    } else if d.y < -size.y * 0.5 {
+        d.y += size.y;
+    }
+    d
+}
+
+pub struct App {
+    exit: bool,
+    boids: Vec<Boid>,
+    playground: Rect,
+    debug_text: String,
+    quiet: bool,
+    marker: Marker,
+    rng: Rand64,
+    count: u16,
+    separation: f64,
+    alignment: f64,
+    cohesion: f64,
+    tick_rate: u64,
+    seed: u128,
+}
+
+impl App {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        terminal_width: u16,
+        terminal_height: u16,
+        marker: Marker,
+        count: u16,
+        seed: u128,
+        separation: f64,
+        alignment: f64,
+        cohesion: f64,
+    ) -> Self {
+        let scale_factor = terminal_height as f32 / terminal_width as f32;
+        let font_scale_factor = 2.0;
+        let width = 200.0;
+        let height = width * scale_factor * font_scale_factor;
+        Self {
+            exit: false,
+            boids: Vec::with_capacity(count as usize),
+            playground: Rect::new(0, 0, width as u16, height as u16),
+            debug_text: String::new(),
+            marker,
+            rng: oorandom::Rand64::new(seed),
+            count,
+            separation,
+            alignment,
+            cohesion,
+            quiet: false,
+            tick_rate: 0,
+            seed,
+        }
+    }
+
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        let tick_rate = Duration::from_millis(tick_rate);
+        let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
+        self.reset();
+
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
+            if event::poll(timeout)? {
+                backoff.reset();
+                match event::read()? {
+                    Event::Key(key) if !options.skip_turn(key) => {
+                        if let Some(action) = input::map_key(key) {
+                            self.apply(action);
+                        }
+                    }
+                    Event::Resize(_columns, _rows) => {
+                        // self.debug_text = format!("{} {}", columns, rows);
+                    }
+                    _ => (),
+                }
+            }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
+
+            if last_tick.elapsed() >= tick_rate {
+                self.on_tick();
+                last_tick = Instant::now();
+            }
+        }
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        format!(
+            "boids --marker {} --tick-rate {} --count {} --seed {} --separation {} --alignment {} --cohesion {}",
+            self.marker,
+            self.tick_rate,
+            self.count,
+            self.seed,
+            self.separation,
+            self.alignment,
+            self.cohesion
+        )
+    }
+
+    fn reset(&mut self) {
+        self.boids.clear();
+        let size = self.size();
+        for _ in 0..self.count {
+            let angle = self.rng.rand_float() * 2.0 * PI;
+            self.boids.push(Boid {
+                position: DVec2::new(
+                    self.rng.rand_float() * size.x,
+                    self.rng.rand_float() * size.y,
+                ),
+                velocity: DVec2::from_angle(angle) * MAX_SPEED,
+            });
+        }
+    }
+
+    fn size(&self) -> DVec2 {
+        DVec2::new(
+            self.playground.right() as f64,
+            self.playground.bottom() as f64,
+        )
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Reset => self.reset(),
+            Action::Quit => self.exit = true,
+            _ => (),
+        }
+    }
+
+    fn on_tick(&mut self) {
+        let size = self.size();
+        // compute every boid's steering from the same snapshot before moving any of them
+        let velocities: Vec<DVec2> = self
+            .boids
+            .iter()
+            .enumerate()
+            .map(|(i, boid)| {
+                let mut separation = DVec2::ZERO;
+                let mut average_velocity = DVec2::ZERO;
+                let mut center_offset = DVec2::ZERO;
+                let mut n_neighbors = 0;
+                for (j, other) in self.boids.iter().enumerate() {
+                    if i == j {
+                        continue;
+                    }
+                    let offset = wrapped_offset(boid.position, other.position, size);
+                    let distance = offset.length();
+                    if distance > VIEW_RADIUS {
+                        continue;
+                    }
+                    if distance < SEPARATION_RADIUS && distance > 0.0 {
+                        separation -= offset / (distance * distance);
+                    }
+                    average_velocity += other.velocity;
+                    center_offset += offset;
+                    n_neighbors += 1;
+                }
+
+                let mut velocity = boid.velocity + separation * self.separation;
+                if n_neighbors > 0 {
+                    let n = n_neighbors as f64;
+                    velocity += (average_velocity / n - boid.velocity) * 0.05 * self.alignment;
+                    velocity += (center_offset / n) * 0.005 * self.cohesion;
+                }
+                let speed = velocity.length();
+                if speed > 0.0 {
+                    velocity *= speed.clamp(MIN_SPEED, MAX_SPEED) / speed;
+                }
+                velocity
+            })
+            .collect();
+
+        for (boid, velocity) in self.boids.iter_mut().zip(velocities) {
+            boid.velocity = velocity;
+            boid.position += velocity;
+            boid.position.x = boid.position.x.rem_euclid(size.x);
+            boid.position.y = boid.position.y.rem_euclid(size.y);
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self.canvas(), frame.area());
+        if !self.quiet && !self.debug_text.is_empty() {
+            let debug_text = Paragraph::new(self.debug_text.clone());
+            frame.render_widget(debug_text, frame.area());
+        }
+    }
+
+    fn canvas(&self) -> impl Widget + '_ {
+        Canvas::default()
+            .marker(self.marker)
+            .paint(|ctx| {
+                for boid in self.boids.iter() {
+                    let direction = boid.velocity.normalize_or(DVec2::X);
+                    let side = direction.perp();
+                    let nose = boid.position + direction * 3.0;
+                    let left = boid.position - direction * 1.5 + side * 1.5;
+                    let right = boid.position - direction * 1.5 - side * 1.5;
+
+                    // color by heading
+                    let heading = direction.y.atan2(direction.x) + PI;
+                    let color = Color::Indexed((heading / (2.0 * PI) * 6.0) as u8 % 6 + 1);
+                    for (p0, p1) in [(nose, left), (left, right), (right, nose)] {
+                        ctx.draw(&Line::new(p0.x, p0.y, p1.x, p1.y, color));
+                    }
+                }
+            })
+            .x_bounds([
+                self.playground.left() as f64,
+                self.playground.right() as f64,
+            ])
+            .y_bounds([
+                self.playground.top() as f64,
+                self.playground.bottom() as f64,
+            ])
+    }
+}
@@ -1,6 +1,11 @@
-use crate::utils::{calc_board_size_scaled, is_quit_key};
+use crate::glyphs::GlyphSet;
+use crate::utils::{
+    calc_board_size_scaled, draw_ascii, draw_palette_swatch, encode_ppm, is_quit_key,
+    value_enum_name, Afterglow, AudioLevel, AudioParam, Backoff, BeatFifo, Mirror, RunOptions,
+    PPM_BLOCK_SIZE,
+};
 use color_eyre::Result;
-use crossterm::event::KeyEventKind;
+use crossterm::event::{KeyCode, KeyEventKind};
 use glam::DVec2;
 use ratatui::{
     crossterm::event::{self, Event},
@@ -14,20 +19,38 @@ use ratatui::{
 };
 use std::time::{Duration, Instant};
 
+// seconds of animation a beat or an --at-tick step is worth
+const TICK_SECS: f64 = 0.016;
+
 pub struct App {
     exit: bool,
     playground: DVec2,
     debug_text: String,
+    quiet: bool,
+    // c shows the colors in use
+    show_palette: bool,
     marker: Marker,
     width: usize,
     height: usize,
-    elapsed_ticks: usize,
+    // animation time, so the speed doesn't depend on the tick rate
+    elapsed_secs: f64,
     n_colors: u8,
+    // --audio-reactive scales the size or n_colors by these
+    size_boost: f64,
+    color_boost: f64,
     a: u32,
     b: u32,
+    // a full turn divided by this is the angle between curves
+    ratio: f64,
+    mirror: Mirror,
+    ascii: bool,
+    persistence: f32,
+    afterglow: Option<Afterglow>,
+    tick_rate: u64,
 }
 
 impl App {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         terminal_width: u16,
         terminal_height: u16,
@@ -35,6 +58,10 @@ impl App {
         n_colors: u8,
         a: u32,
         b: u32,
+        ratio: f64,
+        mirror: Mirror,
+        ascii: bool,
+        persistence: f32,
     ) -> Self {
         let (width, height) = calc_board_size_scaled(marker, terminal_width, terminal_height);
 
@@ -45,87 +72,332 @@ impl App {
             debug_text: String::new(),
             width,
             height,
-            elapsed_ticks: 0,
+            elapsed_secs: 0.0,
             n_colors,
+            size_boost: 1.0,
+            color_boost: 1.0,
             a,
             b,
+            ratio,
+            mirror,
+            ascii,
+            persistence,
+            afterglow: (persistence > 0.0)
+                .then(|| Afterglow::new(persistence as f64, width, height)),
+            quiet: false,
+            show_palette: false,
+            tick_rate: 0,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        let tick_rate = Duration::from_millis(16);
+    /// with `beat_fifo` the bubble only moves on beats read from the pipe,
+    /// with `audio` the speed, size or number of colors follows the audio level
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        beat_fifo: Option<BeatFifo>,
+        audio: Option<AudioLevel>,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
 
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| {
+                self.draw(frame, options.glyphs())
+            })?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
                     Event::Resize(_columns, _rows) => {}
                     _ => (),
                 }
             }
 
             if last_tick.elapsed() >= tick_rate {
+                let mut secs = match &beat_fifo {
+                    Some(beat_fifo) => beat_fifo.take() as f64 * TICK_SECS,
+                    None => last_tick.elapsed().as_secs_f64(),
+                };
+                if let Some(audio) = &audio {
+                    self.size_boost = audio.boost(AudioParam::Amplitude);
+                    self.color_boost = audio.boost(AudioParam::Colors);
+                    secs *= audio.boost(AudioParam::Rotation);
+                }
                 last_tick = Instant::now();
-                self.elapsed_ticks += 1;
+                self.step(secs);
             }
         }
-        Ok(())
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "bubble --marker {} --tick-rate {} -a {} -b {} --ratio {} --mirror {} --n-colors {}",
+            self.marker,
+            self.tick_rate,
+            self.a,
+            self.b,
+            self.ratio,
+            value_enum_name(self.mirror),
+            self.n_colors
+        );
+        if self.ascii {
+            args += " --ascii";
+        }
+        if self.persistence > 0.0 {
+            args += &format!(" --persistence {}", self.persistence);
+        }
+        args
     }
 
     fn handle_key_press(&mut self, key: event::KeyEvent) {
         if key.kind != KeyEventKind::Press {
             return;
         }
-        if is_quit_key(key) {
-            self.exit = true;
+        match key.code {
+            KeyCode::Char('c') if !is_quit_key(key) => self.show_palette = !self.show_palette,
+            _ => {
+                if is_quit_key(key) {
+                    self.exit = true;
+                }
+            }
+        }
+    }
+
+    /// advance the animation by `secs` seconds
+    fn step(&mut self, secs: f64) {
+        self.elapsed_secs += secs;
+        if self.afterglow.is_some() {
+            let frame = self.color_grid();
+            if let Some(afterglow) = &mut self.afterglow {
+                afterglow.blend(|x, y| frame[y][x]);
+            }
         }
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+    fn draw(&self, frame: &mut Frame, glyphs: &GlyphSet) {
+        if self.ascii {
+            // bright where points are
+            let mut levels = vec![vec![0.0; self.width]; self.height];
+            for (x, y, _) in self.points() {
+                if let Some(level) = levels
+                    .get_mut(y as usize)
+                    .and_then(|line| line.get_mut(x as usize))
+                {
+                    *level = 1.0;
+                }
+            }
+            let area = frame.area();
+            draw_ascii(
+                frame.buffer_mut(),
+                area,
+                glyphs,
+                self.width,
+                self.height,
+                |x, y| levels[y][x],
+            );
+        } else {
+            frame.render_widget(self.canvas(), frame.area());
+        }
+        if self.show_palette && !self.quiet {
+            let area = frame.area();
+            draw_palette_swatch(
+                frame.buffer_mut(),
+                area,
+                1..=self.n_colors.saturating_sub(1).max(1),
+            );
+        }
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
     }
 
+    /// every point of the current frame with its color
+    fn points(&self) -> Vec<(f64, f64, u8)> {
+        let r = std::f64::consts::PI * 2.0 / self.ratio;
+
+        let mut x = 0.0;
+        let mut v = 0.0;
+        let t = self.elapsed_secs * 2.5;
+        let size = self.height.min(self.width) as f64 * self.size_boost;
+        // reflect around the same center the points are placed around,
+        // otherwise the halves don't meet and leave a seam
+        let center_x = (self.width / 2) as f64;
+        let center_y = (self.height / 2) as f64;
+        let mut points = Vec::with_capacity((self.a * self.b) as usize);
+
+        for i in 0..self.a {
+            for j in 0..self.b {
+                let a = i as f64 + v;
+                let b = r * i as f64 + x;
+                let u = a.sin() + b.sin();
+                v = a.cos() + b.cos();
+                x = u + t;
+
+                let x_pos = center_x + u * size * 0.24;
+                let y_pos = center_y + v * size * 0.24;
+                // a single color draws everything in color 1
+                let n_colors = (self.n_colors as f64 * self.color_boost).min(u8::MAX as f64) as u32;
+                let n_cycled = n_colors.saturating_sub(1).max(1);
+                let c = (1 + ((i % 15 + j / 36) % n_cycled)) as u8;
+                // skip stuck particle
+                if i == 0 && j == 0 {
+                    continue;
+                }
+                if (self.mirror.horizontal() && x_pos > center_x)
+                    || (self.mirror.vertical() && y_pos > center_y)
+                {
+                    continue;
+                }
+                let mirrored_x = 2.0 * center_x - x_pos;
+                let mirrored_y = 2.0 * center_y - y_pos;
+                points.push((x_pos, y_pos, c));
+                if self.mirror.horizontal() {
+                    points.push((mirrored_x, y_pos, c));
+                }
+                if self.mirror.vertical() {
+                    points.push((x_pos, mirrored_y, c));
+                }
+                if self.mirror == Mirror::Quad {
+                    points.push((mirrored_x, mirrored_y, c));
+                }
+            }
+        }
+        points
+    }
+
+    /// jump to the frame at `tick`
+    pub fn set_tick(&mut self, tick: u64) {
+        self.elapsed_secs = tick as f64 * TICK_SECS;
+    }
+
+    /// the current frame with one color per board cell, canvas y going up
+    fn color_grid(&self) -> Vec<Vec<Option<u8>>> {
+        let mut grid = vec![vec![None; self.width]; self.height];
+        for (x, y, color) in self.points() {
+            let (x, y) = (x.round(), y.round());
+            if x < 0.0 || y < 0.0 || x as usize >= self.width || y as usize >= self.height {
+                continue;
+            }
+            grid[y as usize][x as usize] = Some(color);
+        }
+        grid
+    }
+
+    /// the current frame as a PPM image, one block per board cell
+    pub fn render_to_ppm(&self) -> Vec<u8> {
+        // image rows go down, canvas y goes up
+        let rows: Vec<Vec<u8>> = self
+            .color_grid()
+            .into_iter()
+            .rev()
+            .map(|line| line.into_iter().map(|c| c.unwrap_or(0)).collect())
+            .collect();
+        encode_ppm(&rows, PPM_BLOCK_SIZE)
+    }
+
     fn canvas(&self) -> impl Widget + '_ {
         Canvas::default()
             .marker(self.marker)
             .paint(|ctx| {
-                let r = std::f64::consts::PI * 2.0 / 235.0;
-
-                let mut x = 0.0;
-                let mut v = 0.0;
-                let t = self.elapsed_ticks as f64 * 0.04;
-                let size = self.height.min(self.width) as f64;
-
-                for i in 0..self.a {
-                    for j in 0..self.b {
-                        let a = i as f64 + v;
-                        let b = r * i as f64 + x;
-                        let u = a.sin() + b.sin();
-                        v = a.cos() + b.cos();
-                        x = u + t;
-
-                        let x_pos = (self.width / 2) as f64 + u * size * 0.24;
-                        let y_pos = (self.height / 2) as f64 + v * size * 0.24;
-                        let c = 1 + ((i % 15 + j / 36) % (self.n_colors as u32 - 1));
-                        // skip stuck particle
-                        if i == 0 && j == 0 {
-                            continue;
+                if let Some(afterglow) = &self.afterglow {
+                    for y in 0..self.height {
+                        for x in 0..self.width {
+                            if let Some(color) = afterglow.color(x, y) {
+                                ctx.draw(&Points {
+                                    coords: &[(x as f64, y as f64)],
+                                    color: Color::Indexed(color),
+                                });
+                            }
                         }
-                        ctx.draw(&Points {
-                            coords: &[(x_pos, y_pos)],
-                            color: Color::Indexed(c as u8),
-                        });
                     }
+                    return;
+                }
+                for (x, y, color) in self.points() {
+                    ctx.draw(&Points {
+                        coords: &[(x, y)],
+                        color: Color::Indexed(color),
+                    });
                 }
             })
             .x_bounds([0.0, self.playground.x])
             .y_bounds([0.0, self.playground.y])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    #[test]
+    fn single_color_draws() {
+        for ascii in [false, true] {
+            let mut app = App::new(
+                60,
+                20,
+                Marker::Braille,
+                1,
+                200,
+                200,
+                235.0,
+                Mirror::None,
+                ascii,
+                0.0,
+            );
+            app.show_palette = true;
+            let mut terminal = Terminal::new(TestBackend::new(60, 20)).unwrap();
+            terminal
+                .draw(|frame| app.draw(frame, &GlyphSet::Ascii))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn frames_match_snapshots() {
+        for (n_colors, mirror, ascii) in [
+            (2, Mirror::None, false),
+            (8, Mirror::None, false),
+            (8, Mirror::Quad, false),
+            (8, Mirror::None, true),
+        ] {
+            let mut app = App::new(
+                24,
+                8,
+                Marker::Braille,
+                n_colors,
+                30,
+                30,
+                235.0,
+                mirror,
+                ascii,
+                0.0,
+            );
+            for _ in 0..10 {
+                app.step(TICK_SECS);
+            }
+            let mut terminal = Terminal::new(TestBackend::new(24, 8)).unwrap();
+            terminal
+                .draw(|frame| app.draw(frame, &GlyphSet::Ascii))
+                .unwrap();
+            let name = format!(
+                "bubble_colors{n_colors}_mirror{}_ascii{ascii}",
+                value_enum_name(mirror)
+            );
+            snapshot::assert_snapshot(&name, &snapshot::buffer_text(terminal.backend().buffer()));
+        }
+    }
+}
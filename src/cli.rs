@@ -0,0 +1,932 @@
+use crate::life;
+use crate::maze;
+use crate::pipes3d;
+use crate::sand;
+use crate::splits;
+use crate::utils::RunOptions;
+use crate::utils::{finite_f64, seed_from_str, AudioParam, Mirror, Topology};
+use clap::{builder::RangedU64ValueParser, Parser, Subcommand, ValueEnum};
+use ratatui::symbols::Marker;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Commands,
+
+    #[command(flatten)]
+    pub options: RunOptions,
+
+    /// Config file with a [keys] table of extra key bindings (rings, boids, ant).
+    /// Defaults to $XDG_CONFIG_HOME/terminal-toys/config.toml if it exists
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Render a single frame to a PPM image instead of running (tunnel, bubble)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub output_ppm: Option<PathBuf>,
+
+    /// Tick to render with --output-ppm
+    #[arg(long, global = true, value_name = "TICK", default_value_t = 0)]
+    pub at_tick: u64,
+
+    /// Named pipe to drive the animation from, every byte written advances one tick (tunnel, bubble, cube)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub beat_fifo: Option<PathBuf>,
+
+    /// Drive a setting from the level of the default audio input (tunnel, bubble, cube).
+    /// Needs the audio feature, pick a loopback/monitor device to follow the system's output
+    #[arg(long, global = true, default_value_t = false)]
+    pub audio_reactive: bool,
+
+    /// Setting driven by --audio-reactive
+    #[arg(
+        long,
+        global = true,
+        value_name = "PARAM",
+        value_enum,
+        default_value_t = AudioParam::Rotation
+    )]
+    pub audio_param: AudioParam,
+
+    /// Frames per second, overrides the tick rate with 1000/N milliseconds.
+    /// Toys with a speed multiplier still take that many steps per frame
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub fps: Option<u64>,
+
+    /// Draw in HEIGHT lines below the prompt instead of taking over the screen, and leave
+    /// the last frame in the scrollback
+    #[arg(long, global = true, value_name = "HEIGHT", value_parser = clap::value_parser!(u16).range(1..))]
+    pub inline: Option<u16>,
+
+    /// Print a command line with the current settings when quitting
+    #[arg(long, global = true, default_value_t = false)]
+    pub print_config_on_exit: bool,
+
+    /// When quitting, print the command line to reproduce the scene, seed included, and a
+    /// link to the project
+    #[arg(short, long, global = true, default_value_t = false)]
+    pub verbose: bool,
+
+    /// Write the characters of every drawn frame to DIR/frame_000001.txt and so on, for debugging
+    #[arg(long, global = true, value_name = "DIR")]
+    pub dump_frames: Option<PathBuf>,
+
+    /// Stop writing frames for --dump-frames after this many
+    #[arg(long, global = true, value_name = "N", default_value_t = 1000)]
+    pub max_frames: u64,
+
+    /// After quitting, add the frames from --dump-frames again in reverse so they loop
+    /// forward and back. Only the frames of this run are used
+    #[arg(long, global = true, default_value_t = false, requires = "dump_frames")]
+    pub ping_pong: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Bouncy balls!
+    Balls {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// Number of balls to spawn
+        #[arg(short = 'n', long, value_name = "BALLS", default_value_t = 50)]
+        max_balls: u16,
+
+        /// Add a wall at the top of the screen to keep balls contained
+        #[arg(long, default_value_t = false)]
+        ceiling: bool,
+
+        /// Let balls leaving an edge come back in at the opposite one instead of bouncing
+        #[arg(long, default_value_t = false, conflicts_with = "ceiling")]
+        wrap: bool,
+
+        /// Leave a fading trail of the last N positions behind each ball
+        #[arg(long, value_name = "N")]
+        trail: Option<usize>,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 99, value_parser = parse_seed::<u128>)]
+        seed: u128,
+
+        /// Random change in speed added on every bounce, keeps the motion from settling into loops
+        #[arg(short, long, value_name = "AMOUNT", default_value_t = 0.0, value_parser = finite_f64)]
+        jitter: f64,
+    },
+    /// Flocking boids
+    Boids {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// Number of boids
+        #[arg(short = 'n', long, value_name = "BOIDS", default_value_t = 100)]
+        count: u16,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 99, value_parser = parse_seed::<u128>)]
+        seed: u128,
+
+        /// How strongly boids avoid crowding each other
+        #[arg(short = 'p', long, value_name = "WEIGHT", default_value_t = 1.5, value_parser = finite_f64)]
+        separation: f64,
+
+        /// How strongly boids match their neighbors' heading
+        #[arg(short, long, value_name = "WEIGHT", default_value_t = 1.0, value_parser = finite_f64)]
+        alignment: f64,
+
+        /// How strongly boids move towards their neighbors
+        #[arg(short, long, value_name = "WEIGHT", default_value_t = 1.0, value_parser = finite_f64)]
+        cohesion: f64,
+    },
+    /// 3d pipe screensaver
+    Pipes3d {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
+        marker: Marker,
+
+        /// Number of segments to generate before reset
+        #[arg(short = 'n', long, value_name = "SEGMENTS", default_value_t = 2000)]
+        max_segments: u32,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 8
+        )]
+        tick_rate: u64,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 99, value_parser = parse_seed::<u64>)]
+        seed: u64,
+
+        /// Use orthographic projection
+        #[arg(short, long, default_value_t = false)]
+        orthographic: bool,
+
+        /// Camera speed, adjust with [ and ]
+        #[arg(short = 'x', long, value_name = "SPEED", default_value_t = 4.0, value_parser = finite_f64)]
+        camera_speed: f64,
+
+        /// Instead of resetting, delete earlier segments
+        #[arg(short, long, default_value_t = false)]
+        rotate: bool,
+
+        /// Starting FOV, smaller is wider
+        #[arg(long, value_name = "FOV", default_value_t = 0.01, value_parser = finite_f64)]
+        fov: f64,
+
+        /// Amount the FOV changes per a/d key press
+        #[arg(long, value_name = "STEP", default_value_t = 0.001, value_parser = finite_f64)]
+        fov_step: f64,
+
+        /// How segments are colored
+        #[arg(long, value_name = "MODE", value_enum, default_value_t = pipes3d::ColorMode::Run)]
+        color_mode: pipes3d::ColorMode,
+
+        /// Draw in red and cyan for 3d glasses
+        #[arg(long, default_value_t = false)]
+        anaglyph: bool,
+
+        /// Distance between the eyes with --anaglyph
+        #[arg(long, value_name = "DISTANCE", default_value_t = 0.3, value_parser = finite_f64)]
+        eye_separation: f64,
+
+        /// Fly the camera yourself: WASD and arrows move and turn, e/q up/down, Esc quits
+        #[arg(long, default_value_t = false)]
+        free_cam: bool,
+
+        /// Draw lines this many pixels wide (1 to 5)
+        #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=5))]
+        thickness: u8,
+
+        /// Draw far lines first so nearer ones end up on top
+        #[arg(long, default_value_t = false)]
+        z_sort: bool,
+
+        /// Skip redrawing while the projected pipes stay the same, e.g. once the camera settles
+        #[arg(long, default_value_t = false)]
+        skip_unchanged: bool,
+    },
+    /// Classic 2d pipe screensaver
+    Pipes2d {
+        /// Draw pipes with this marker type instead of box-drawing characters
+        /// (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE")]
+        marker: Option<Marker>,
+
+        /// Number of segments to generate before reset
+        #[arg(short = 'n', long, value_name = "SEGMENTS", default_value_t = 2000)]
+        max_segments: u32,
+
+        /// Number of pipes growing at the same time
+        #[arg(short, long, value_name = "N", default_value_t = 3)]
+        pipes: u16,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 99, value_parser = parse_seed::<u64>)]
+        seed: u64,
+    },
+    /// Lines that split after a while
+    Splits {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// Lines have a random rotation
+        #[arg(short, long, default_value_t = false)]
+        rotate: bool,
+
+        #[arg(short = 'n', long, value_name = "WALKERS", default_value_t = 80)]
+        max_walkers: u16,
+
+        /// Number of colors
+        #[arg(short = 'c', long, value_name = "N", default_value_t = 11, value_parser = clap::value_parser!(u8).range(1..))]
+        n_colors: u8,
+
+        /// Keep going by recycling walkers that left the screen instead of resetting
+        #[arg(long, default_value_t = false)]
+        continuous: bool,
+
+        /// Walkers bounce off the edges of the screen instead of stopping there
+        #[arg(long, default_value_t = false)]
+        bounce: bool,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 99, value_parser = parse_seed::<u128>)]
+        seed: u128,
+
+        /// Draw lines this many pixels wide (1 to 5)
+        #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=5))]
+        thickness: u8,
+
+        /// Points of trail kept per walker, older ones disappear. Bounds memory and drawing
+        /// with --bounce, where walkers never leave. A walker splitting off starts a new trail
+        #[arg(long, value_name = "N", default_value_t = splits::DEFAULT_MAX_HISTORY, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        max_history: usize,
+    },
+    /// Game of life
+    Life {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 32
+        )]
+        tick_rate: u64,
+
+        /// Width of board (default: terminal width)
+        #[arg(short, long, value_name = "WIDTH", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        width: Option<usize>,
+
+        /// Height of board (default: follows the width and terminal shape)
+        #[arg(long, value_name = "HEIGHT", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        height: Option<usize>,
+
+        /// Ratio of live cells to start with (0.0 to 1.0)
+        #[arg(short, value_name = "RATIO", default_value_t = 0.5, value_parser = parse_ratio)]
+        n: f32,
+
+        /// Number of colors
+        #[arg(short = 'c', long, value_name = "N", default_value_t = 12, value_parser = clap::value_parser!(u8).range(1..))]
+        n_colors: u8,
+
+        /// Edge behavior (torus, bounded, mirror)
+        #[arg(short, long, value_name = "TOPOLOGY", value_enum, default_value_t = Topology::Torus)]
+        edges: Topology,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 3, value_parser = parse_seed::<u128>)]
+        seed: u128,
+
+        /// Draw with ASCII characters instead of the marker
+        #[arg(long, default_value_t = false)]
+        ascii: bool,
+
+        /// Keep running once the board settles instead of starting over
+        #[arg(long, default_value_t = false)]
+        no_reset: bool,
+
+        /// Outline dead cells in a dim color when the board is drawn with blocks (--width)
+        #[arg(long, default_value_t = false)]
+        show_grid: bool,
+
+        /// How a newborn cell picks its color from its neighbors
+        #[arg(long, value_name = "RULE", value_enum, default_value_t = life::ColorRule::First)]
+        color_rule: life::ColorRule,
+
+        /// Generations to keep for stepping back with b (space resumes), 0 to keep none
+        #[arg(long, value_name = "N", default_value_t = life::DEFAULT_HISTORY)]
+        history: usize,
+
+        /// Stop on the final frame once the board settles instead of starting over, and print
+        /// the generations, population and period when quitting. r starts over
+        #[arg(long, default_value_t = false)]
+        freeze_on_stable: bool,
+    },
+    /// Maze generation and solving
+    Maze {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// Width of board (default: terminal width)
+        #[arg(short, long, value_name = "WIDTH")]
+        width: Option<usize>,
+
+        /// Speed multiplier
+        #[arg(short = 'x', long, value_name = "MULT", default_value_t = 4)]
+        speed: usize,
+
+        /// Generation algorithm
+        #[arg(short, long, value_name = "ALGORITHM", value_enum, default_value_t = maze::Algorithm::Backtracker)]
+        algorithm: maze::Algorithm,
+
+        /// Solving algorithm
+        #[arg(short = 'l', long, value_name = "SOLVER", value_enum, default_value_t = maze::Solver::Bfs)]
+        solver: maze::Solver,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 99, value_parser = parse_seed::<u128>)]
+        seed: u128,
+    },
+    /// Falling sand
+    Sand {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 8
+        )]
+        tick_rate: u64,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 0, value_parser = parse_seed::<u128>)]
+        seed: u128,
+
+        /// RNG seed for the obstacle layout, keeps the same layout on every reset [default: --seed]
+        #[arg(long, value_name = "SEED", value_parser = parse_seed::<u128>)]
+        obstacle_seed: Option<u128>,
+
+        /// Speed multiplier, steps per frame (--fps only changes how often it redraws)
+        #[arg(short = 'x', long, value_name = "MULT", default_value_t = 1)]
+        speed: usize,
+
+        /// Number of obstacles
+        #[arg(short, long, value_name = "N", default_value_t = 40)]
+        obstacles: usize,
+
+        /// Length of obstacles
+        #[arg(short = 'l', long, value_name = "N", default_value_t = 5)]
+        obstacle_len: usize,
+
+        /// Color index of obstacles, so they stand out from the sand
+        #[arg(long, value_name = "INDEX", default_value_t = 8)]
+        obstacle_color: u8,
+
+        /// Generate hills and caverns from noise instead of line obstacles
+        #[arg(long, default_value_t = false)]
+        terrain: bool,
+
+        /// How much of the middle of the screen --terrain fills (0.0 to 1.0)
+        #[arg(long, value_name = "RATIO", default_value_t = 0.4, value_parser = parse_ratio)]
+        terrain_density: f32,
+
+        /// Let grains slide over the top of obstacles so piles can bury them
+        #[arg(long, default_value_t = false)]
+        burying: bool,
+
+        /// Average number of particles to spawn before changing spawn point
+        #[arg(short, long, value_name = "N", default_value_t = 100)]
+        particles: u64,
+
+        /// Grains each emitter drops per step (0.0 to 1.0), 1 pours twice as fast as the
+        /// default. --speed runs more steps per frame, so the flow per frame grows with both
+        #[arg(long, value_name = "RATE", default_value_t = 0.5, value_parser = parse_ratio)]
+        spawn_rate: f32,
+
+        /// Flip after N ticks
+        #[arg(short, long, value_name = "N")]
+        flip_after: Option<u32>,
+
+        /// reset after sand emptied N times
+        #[arg(short, long, value_name = "N", default_value_t = 3)]
+        reset: usize,
+
+        /// Number of emitters spawning particles at the same time
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        emitters: usize,
+
+        /// How emitters move along the top
+        #[arg(long, value_name = "MODE", value_enum, default_value_t = sand::EmitterMode::Point)]
+        emitter_mode: sand::EmitterMode,
+
+        /// Draw with ASCII characters instead of the marker
+        #[arg(long, default_value_t = false)]
+        ascii: bool,
+
+        /// Horizontal bias of falling sand (-1.0 to 1.0), adjust with , and .
+        #[arg(
+            short,
+            long,
+            value_name = "WIND",
+            default_value_t = 0.0,
+            allow_negative_numbers = true,
+            value_parser = parse_wind
+        )]
+        wind: f64,
+
+        /// Blend the colors of grains landing next to grains of another color
+        #[arg(long, default_value_t = false)]
+        mix: bool,
+
+        /// Stop at a full pile instead of emptying and starting over
+        #[arg(long, default_value_t = false)]
+        no_reset: bool,
+
+        /// Show falling and settled grains and how full the board is, toggle with i
+        #[arg(long, default_value_t = false)]
+        stats: bool,
+    },
+    /// Warp speed starfield
+    Stars {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// Number of stars
+        #[arg(short = 'n', long, value_name = "STARS", default_value_t = 300)]
+        count: u16,
+
+        /// Speed
+        #[arg(short = 'x', long, value_name = "SPEED", default_value_t = 0.5, value_parser = finite_f64)]
+        speed: f64,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 99, value_parser = parse_seed::<u128>)]
+        seed: u128,
+    },
+    /// Rain collecting in puddles
+    Rain {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// Average number of drops per tick
+        #[arg(short, long, value_name = "DROPS", default_value_t = 1.0, value_parser = finite_f64)]
+        intensity: f64,
+
+        /// Horizontal drift of falling drops (-1.0 to 1.0)
+        #[arg(
+            short,
+            long,
+            value_name = "WIND",
+            default_value_t = 0.0,
+            allow_negative_numbers = true,
+            value_parser = parse_wind
+        )]
+        wind: f64,
+
+        /// Number of obstacles
+        #[arg(short, long, value_name = "N", default_value_t = 20)]
+        obstacles: usize,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 0, value_parser = parse_seed::<u128>)]
+        seed: u128,
+    },
+    /// Rotating tunnel
+    Tunnel {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
+        marker: Marker,
+
+        #[arg(
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 8
+        )]
+        tick_rate: u64,
+
+        /// Number of colors
+        #[arg(short, long, value_name = "N", default_value_t = 16)]
+        n_colors: u8,
+
+        /// Rotation speed
+        #[arg(short = 'x', long, value_name = "SPEED", default_value_t = 1.0, value_parser = finite_f64)]
+        speed: f64,
+
+        /// Amount of depth (0, 1, 2)
+        #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=2))]
+        depth: u8,
+
+        /// Twisting tunnel
+        #[arg(short, long, default_value_t = false)]
+        twist: bool,
+
+        /// Mirror the picture (none, horizontal, vertical, quad)
+        #[arg(long, value_name = "MODE", value_enum, default_value_t = Mirror::None)]
+        mirror: Mirror,
+
+        /// Draw with ASCII characters instead of the marker
+        #[arg(long, default_value_t = false)]
+        ascii: bool,
+
+        /// Blend each frame into the previous ones so motion leaves a glow (0.0 to 1.0)
+        #[arg(long, value_name = "RATIO", default_value_t = 0.0, value_parser = parse_ratio)]
+        persistence: f32,
+
+        /// Shade smoothly between the color bands on truecolor terminals
+        #[arg(long, default_value_t = false, conflicts_with = "persistence")]
+        smooth: bool,
+    },
+    /// Langton's Ant
+    Ant {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// Width of board (default: terminal width)
+        #[arg(short, long, value_name = "WIDTH", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        width: Option<usize>,
+
+        /// Height of board (default: follows the width and terminal shape)
+        #[arg(long, value_name = "HEIGHT", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        height: Option<usize>,
+
+        /// Speed multiplier
+        #[arg(short = 'x', long, value_name = "MULT", default_value_t = 1)]
+        speed: usize,
+
+        /// Number of colors
+        #[arg(short, long, value_name = "N", default_value_t = 16, value_parser = clap::value_parser!(u8).range(2..))]
+        n_colors: u8,
+
+        /// Step distance = color
+        #[arg(short, long, default_value_t = false)]
+        dist_by_color: bool,
+
+        /// Fill in path
+        #[arg(short, long, default_value_t = false)]
+        filled: bool,
+
+        /// Edge behavior (torus, bounded, mirror)
+        #[arg(short, long, value_name = "TOPOLOGY", value_enum, default_value_t = Topology::Torus)]
+        edges: Topology,
+
+        /// Pattern of starting active blocks [default: random]
+        #[arg(short, long, value_name = "INDEX", value_parser = clap::value_parser!(u8).range(0..6))]
+        pattern: Option<u8>,
+
+        /// Pattern length [default: random]
+        #[arg(short = 'l', long, value_name = "N")]
+        pattern_len: Option<usize>,
+
+        /// RNG seed, or "random" for a new one every run
+        #[arg(short, long, value_name = "SEED", default_value_t = 99, value_parser = parse_seed::<u128>)]
+        seed: u128,
+
+        /// Stop once the board is saturated instead of starting over
+        #[arg(long, default_value_t = false)]
+        no_reset: bool,
+
+        /// Scroll the world to follow the ant instead of starting over, so its highway grows forever
+        #[arg(long, default_value_t = false)]
+        scroll: bool,
+
+        /// Ratio of visited cells (0.0 to 1.0) at which the board counts as saturated
+        #[arg(long, value_name = "RATIO", default_value_t = 0.9, value_parser = parse_ratio)]
+        reset_threshold: f32,
+
+        /// Move in eight directions, turning 45 degrees right on colored cells and left on
+        /// blank ones instead of 90
+        #[arg(long, default_value_t = false)]
+        diagonal: bool,
+    },
+    /// Bubble universe by A-na5 / ｱ_ﾅ
+    Bubble {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 16
+        )]
+        tick_rate: u64,
+
+        /// Number of curves. Every frame draws a*b points, so larger values get slow
+        #[arg(short, long = "iterations-n", value_name = "N", default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+        a: u32,
+
+        /// Points along each curve
+        #[arg(short, long = "iterations-m", value_name = "N", default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+        b: u32,
+
+        /// Neighboring curves are a full turn divided by this apart
+        #[arg(long, value_name = "DIVISOR", default_value_t = 235.0, value_parser = parse_positive)]
+        ratio: f64,
+
+        /// Mirror the picture (none, horizontal, vertical, quad)
+        #[arg(long, value_name = "MODE", value_enum, default_value_t = Mirror::None)]
+        mirror: Mirror,
+
+        /// Number of colors
+        #[arg(short, long, value_name = "N", default_value_t = 16, value_parser = clap::value_parser!(u8).range(1..))]
+        n_colors: u8,
+
+        /// Draw with ASCII characters instead of the marker
+        #[arg(long, default_value_t = false)]
+        ascii: bool,
+
+        /// Blend each frame into the previous ones so motion leaves a glow (0.0 to 1.0)
+        #[arg(long, value_name = "RATIO", default_value_t = 0.0, value_parser = parse_ratio)]
+        persistence: f32,
+    },
+    /// Rotating sine wave cube
+    Cube {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 8
+        )]
+        tick_rate: u64,
+
+        /// Use orthographic projection
+        #[arg(short, long, default_value_t = false)]
+        orthographic: bool,
+
+        #[arg(short, long, value_name = "SPEED", default_value_t = 1.0, value_parser = finite_f64)]
+        x_rotation_speed: f64,
+
+        #[arg(short, long, value_name = "SPEED", default_value_t = 0.5, value_parser = finite_f64)]
+        y_rotation_speed: f64,
+
+        #[arg(short, long, value_name = "SPEED", default_value_t = 0.25, value_parser = finite_f64)]
+        z_rotation_speed: f64,
+
+        /// Amplitude of sine waves, 0 for a plain rotating cube
+        #[arg(short, long, value_name = "AMPLITUDE", default_value_t = 1.2, value_parser = finite_f64)]
+        amplitude: f64,
+
+        /// Frequency of sine waves
+        #[arg(short, long, value_name = "FREQUENCY", default_value_t = 1.0, value_parser = finite_f64)]
+        frequency: f64,
+
+        /// Speed of sine wave phase shift
+        #[arg(short, long, value_name = "SPEED", default_value_t = 1.0, value_parser = finite_f64)]
+        speed: f64,
+
+        /// Just rotate, same as --amplitude 0
+        #[arg(long, default_value_t = false, conflicts_with = "amplitude")]
+        no_wave: bool,
+
+        /// Color change speed
+        #[arg(short, long, value_name = "SPEED", value_parser = finite_f64)]
+        color_speed: Option<f64>,
+
+        /// Starting FOV, smaller is wider
+        #[arg(long, value_name = "FOV", default_value_t = 0.01, value_parser = finite_f64)]
+        fov: f64,
+
+        /// Amount the FOV changes per a/d key press
+        #[arg(long, value_name = "STEP", default_value_t = 0.001, value_parser = finite_f64)]
+        fov_step: f64,
+
+        /// Draw in red and cyan for 3d glasses
+        #[arg(long, default_value_t = false)]
+        anaglyph: bool,
+
+        /// Distance between the eyes with --anaglyph
+        #[arg(long, value_name = "DISTANCE", default_value_t = 1.0, value_parser = finite_f64)]
+        eye_separation: f64,
+
+        /// Draw lines this many pixels wide (1 to 5)
+        #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=5))]
+        thickness: u8,
+
+        /// Draw far lines first so nearer ones end up on top
+        #[arg(long, default_value_t = false)]
+        z_sort: bool,
+
+        /// Fill the outer faces with flat shading instead of drawing lines
+        #[arg(long, default_value_t = false, conflicts_with = "anaglyph")]
+        solid: bool,
+
+        /// Segments along each line, fewer draw faster and more make smoother waves
+        #[arg(long, value_name = "N", default_value_t = 48, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        resolution: usize,
+
+        /// Draw the 12 edges of a box instead of the grid of lines
+        #[arg(long, default_value_t = false, conflicts_with = "solid")]
+        wireframe: bool,
+
+        /// Skip redrawing while the projected cube stays the same, e.g. with no rotation or wave
+        #[arg(long, default_value_t = false)]
+        skip_unchanged: bool,
+    },
+    /// Sphere made out of shifting rings
+    Rings {
+        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
+        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
+        marker: Marker,
+
+        #[arg(
+            short,
+            long,
+            value_name = "MILLISECONDS",
+            conflicts_with = "fps",
+            default_value_t = 8
+        )]
+        tick_rate: u64,
+
+        /// Use orthographic projection
+        #[arg(short, long, default_value_t = false)]
+        orthographic: bool,
+
+        #[arg(short, long, value_name = "SPEED", default_value_t = 0.5, value_parser = finite_f64)]
+        x_rotation_speed: f64,
+
+        #[arg(short, long, value_name = "SPEED", default_value_t = 0.25, value_parser = finite_f64)]
+        y_rotation_speed: f64,
+
+        #[arg(short, long, value_name = "SPEED", default_value_t = 0.125, value_parser = finite_f64)]
+        z_rotation_speed: f64,
+
+        /// Amplitude of sine waves
+        #[arg(short, long, value_name = "AMPLITUDE", default_value_t = 5.0, value_parser = finite_f64)]
+        amplitude: f64,
+
+        /// Frequency of sine waves
+        #[arg(short, long, value_name = "FREQUENCY", default_value_t = 10.0, value_parser = finite_f64)]
+        frequency: f64,
+
+        /// Speed of sine wave phase shift
+        #[arg(short, long, value_name = "SPEED", default_value_t = 0.1, value_parser = finite_f64)]
+        speed: f64,
+
+        /// Zoom
+        #[arg(
+            long,
+            value_name = "ZOOM",
+            default_value_t = 0.0,
+            allow_negative_numbers = true,
+            value_parser = finite_f64
+        )]
+        zoom: f64,
+    },
+    /// Show every toy with its default settings in turn, n skips to the next one
+    Cycle {
+        /// Seconds to show each toy for
+        #[arg(short, long, value_name = "SECONDS", default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+
+        /// Shuffle the toys with this seed instead of going in order
+        #[arg(short, long, value_name = "SEED", value_parser = parse_seed::<u64>)]
+        seed: Option<u64>,
+    },
+    /// Print version and terminal details for bug reports
+    Info {
+        /// Output format
+        #[arg(long, value_name = "FORMAT", value_enum, default_value_t = InfoFormat::Text)]
+        format: InfoFormat,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum InfoFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// parse a --seed into the integer type the toy seeds its rng with, see [`seed_from_str`]
+fn parse_seed<T: TryFrom<u128>>(s: &str) -> Result<T, String> {
+    T::try_from(seed_from_str(s)?).map_err(|_| format!("{s} is too large for this --seed"))
+}
+
+/// parse a finite float above 0
+fn parse_positive(s: &str) -> Result<f64, String> {
+    let value = finite_f64(s)?;
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("{value} is not above 0"))
+    }
+}
+
+/// parse a finite float between -1.0 and 1.0
+fn parse_wind(s: &str) -> Result<f64, String> {
+    let wind = finite_f64(s)?;
+    if (-1.0..=1.0).contains(&wind) {
+        Ok(wind)
+    } else {
+        Err(format!("{wind} is not in -1.0..=1.0"))
+    }
+}
+
+/// parse a float between 0.0 and 1.0
+fn parse_ratio(s: &str) -> Result<f32, String> {
+    let ratio: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err(format!("{ratio} is not in 0.0..=1.0"))
+    }
+}
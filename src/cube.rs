@@ -1,4 +1,9 @@
-use crate::utils::is_quit_key;
+use crate::gamepad;
+use crate::input::{self, Action};
+use crate::utils::{
+    calculate_hash, sort_back_to_front, AudioLevel, AudioParam, Backoff, BeatFifo, RunOptions,
+    Scene, ToScreenPos, FOV_MAX, FOV_MIN,
+};
 use color_eyre::Result;
 use crossterm::event::KeyEventKind;
 use glam::{DVec2, DVec3};
@@ -7,40 +12,59 @@ use ratatui::{
     layout::Rect,
     style::Color,
     symbols::Marker,
-    widgets::{
-        canvas::{Canvas, Line},
-        Paragraph, Widget,
-    },
+    widgets::{canvas::Canvas, Paragraph, Widget},
     DefaultTerminal, Frame,
 };
 use std::time::{Duration, Instant};
 
-trait ToScreenPos {
-    fn to_screen_position(self, playgrground: Rect, val: f64) -> DVec2;
-    fn to_screen_position_orthographic(self, playgrground: Rect) -> DVec2;
-}
-
-impl ToScreenPos for DVec3 {
-    fn to_screen_position(self, playground: Rect, val: f64) -> DVec2 {
-        let z = self.z;
-        let x = (self.x) / (val * z);
-        let y = (self.y) / (val * z);
+// seconds of animation a beat is worth
+const TICK_SECS: f64 = 0.008;
+const WAVE_STEP: f64 = 0.1;
+// lines per side of the grid, as built in App::new
+const GRID_SIDE: usize = 5;
+// half the side of the box, the grid spans the same
+const HALF_SIDE: f64 = 14.4;
+// larger waves leave the screen
+const AMPLITUDE_MAX: f64 = 10.0;
+const FREQUENCY_MAX: f64 = 5.0;
 
-        DVec2 {
-            x: x + playground.right() as f64 * 0.5,
-            y: y + playground.bottom() as f64 * 0.5,
+/// outer faces of the grid as quads of indices into the points, for --solid
+fn face_quads(line_len: usize) -> Vec<[usize; 4]> {
+    let index = |x: usize, y: usize, z: usize| (x * GRID_SIDE + y) * line_len + z;
+    let last = GRID_SIDE - 1;
+    // lines along the rim of the grid, in order around it
+    let rim: Vec<(usize, usize)> = (0..last)
+        .map(|i| (i, 0))
+        .chain((0..last).map(|i| (last, i)))
+        .chain((0..last).map(|i| (last - i, last)))
+        .chain((0..last).map(|i| (0, last - i)))
+        .collect();
+    let mut quads = Vec::new();
+    for (k, &(x0, y0)) in rim.iter().enumerate() {
+        let (x1, y1) = rim[(k + 1) % rim.len()];
+        for z in 0..line_len - 1 {
+            quads.push([
+                index(x0, y0, z),
+                index(x1, y1, z),
+                index(x1, y1, z + 1),
+                index(x0, y0, z + 1),
+            ]);
         }
     }
-
-    fn to_screen_position_orthographic(self, playground: Rect) -> DVec2 {
-        let x = 3.0 * self.x + 0.4 * self.z * 3.0;
-        let y = 3.0 * self.y + 0.4 * self.z * 3.0;
-
-        DVec2 {
-            x: x + playground.right() as f64 * 0.5,
-            y: y + playground.bottom() as f64 * 0.5,
+    // end caps
+    for z in [0, line_len - 1] {
+        for x in 0..last {
+            for y in 0..last {
+                quads.push([
+                    index(x, y, z),
+                    index(x + 1, y, z),
+                    index(x + 1, y + 1, z),
+                    index(x, y + 1, z),
+                ]);
+            }
         }
     }
+    quads
 }
 
 fn rotate_z(point: DVec3, angle: f64) -> DVec3 {
@@ -70,19 +94,40 @@ fn rotate_y(point: DVec3, angle: f64) -> DVec3 {
 pub struct App {
     exit: bool,
     playground: Rect,
-    tick_count: u64,
+    // animation time, so the speed doesn't depend on the tick rate
+    elapsed_secs: f64,
     debug_text: String,
+    quiet: bool,
     marker: Marker,
     orthographic: bool,
     val: f64,
+    fov_step: f64,
     points: Vec<DVec3>,
     x_rotation_speed: f64,
     y_rotation_speed: f64,
     z_rotation_speed: f64,
     amplitude: f64,
+    // --audio-reactive amplitude scales the wave by this
+    amplitude_boost: f64,
     frequency: f64,
+    // values from the command line, restored with r
+    launch_amplitude: f64,
+    launch_frequency: f64,
     speed: f64,
     color_speed: Option<f64>,
+    anaglyph: bool,
+    eye_separation: f64,
+    thickness: u8,
+    z_sort: bool,
+    solid: bool,
+    // segments along each line, points per line is one more
+    resolution: usize,
+    faces: Vec<[usize; 4]>,
+    // the 12 edges of a box instead of the grid
+    wireframe: bool,
+    // only redraw when the projected scene changes
+    skip_unchanged: bool,
+    tick_rate: u64,
 }
 
 impl App {
@@ -99,20 +144,52 @@ impl App {
         frequency: f64,
         speed: f64,
         color_speed: Option<f64>,
+        fov: f64,
+        fov_step: f64,
+        anaglyph: bool,
+        eye_separation: f64,
+        thickness: u8,
+        z_sort: bool,
+        solid: bool,
+        resolution: usize,
+        wireframe: bool,
+        skip_unchanged: bool,
     ) -> Self {
         let scale_factor = terminal_height as f32 / terminal_width as f32;
         let font_scale_factor = 2.0;
         let width = 200.0;
         let height = width * scale_factor * font_scale_factor;
 
+        let resolution = resolution.max(1);
         let mut points = Vec::new();
-        for x in (-24..=24).step_by(12) {
-            for y in (-24..=24).step_by(12) {
-                for z in (-24..=24).step_by(1) {
-                    let x = 0.6 * x as f64;
-                    let y = 0.6 * y as f64;
-                    let z = 0.6 * z as f64;
-                    points.push(DVec3::new(x, y, z));
+        if wireframe {
+            // every pair of corners one axis apart, split into segments so the wave bends them
+            let corners: Vec<DVec3> = (0..8)
+                .map(|i| {
+                    let sign = |bit: i32| if i & bit == 0 { -1.0 } else { 1.0 };
+                    HALF_SIDE * DVec3::new(sign(1), sign(2), sign(4))
+                })
+                .collect();
+            for (i, &a) in corners.iter().enumerate() {
+                for bit in [1, 2, 4] {
+                    if i & bit != 0 {
+                        continue;
+                    }
+                    let b = corners[i | bit];
+                    for step in 0..=resolution {
+                        points.push(a.lerp(b, step as f64 / resolution as f64));
+                    }
+                }
+            }
+        } else {
+            for x in (-24..=24).step_by(12) {
+                for y in (-24..=24).step_by(12) {
+                    for i in 0..=resolution {
+                        let x = HALF_SIDE * x as f64 / 24.0;
+                        let y = HALF_SIDE * y as f64 / 24.0;
+                        let z = HALF_SIDE * (-1.0 + 2.0 * i as f64 / resolution as f64);
+                        points.push(DVec3::new(x, y, z));
+                    }
                 }
             }
         }
@@ -120,115 +197,310 @@ impl App {
         Self {
             exit: false,
             playground: Rect::new(0, 0, width as u16, height as u16),
-            tick_count: 0,
+            elapsed_secs: 0.0,
             marker,
             debug_text: String::new(),
             orthographic,
-            val: 0.01,
+            val: fov.clamp(FOV_MIN, FOV_MAX),
+            fov_step,
             points,
             x_rotation_speed,
             y_rotation_speed,
             z_rotation_speed,
             amplitude,
+            amplitude_boost: 1.0,
             frequency,
+            launch_amplitude: amplitude,
+            launch_frequency: frequency,
             speed,
             color_speed,
+            anaglyph,
+            eye_separation,
+            thickness,
+            z_sort,
+            solid,
+            faces: if solid {
+                face_quads(resolution + 1)
+            } else {
+                Vec::new()
+            },
+            resolution,
+            wireframe,
+            skip_unchanged,
+            quiet: false,
+            tick_rate: 0,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal, tick_rate: u64) -> Result<()> {
+    /// with `beat_fifo` the cube only moves on beats read from the pipe,
+    /// with `audio` the speed or height of the wave follows the audio level
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        beat_fifo: Option<BeatFifo>,
+        audio: Option<AudioLevel>,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
         let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
+        // every frame is needed for --dump-frames
+        let skip_unchanged = self.skip_unchanged && options.frame_dumper.is_none();
+        let mut last_scene_hash = None;
 
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            let scene = self.scene();
+            let scene_hash = calculate_hash(&(scene.hash(), &self.debug_text));
+            if !skip_unchanged || last_scene_hash != Some(scene_hash) {
+                options.draw(&mut terminal, &mut backoff, |frame| {
+                    self.draw(frame, &scene)
+                })?;
+                last_scene_hash = Some(scene_hash);
+            }
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
                     Event::Resize(_columns, _rows) => {
-                        // self.debug_text = format!("{} {}", columns, rows);
+                        // redraw at the new size
+                        last_scene_hash = None;
                     }
                     _ => (),
                 }
             }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
 
             if last_tick.elapsed() >= tick_rate {
+                let step = match &beat_fifo {
+                    Some(beat_fifo) => beat_fifo.take() as f64 * TICK_SECS,
+                    None => last_tick.elapsed().as_secs_f64(),
+                };
+                if let Some(audio) = &audio {
+                    self.amplitude_boost = audio.boost(AudioParam::Amplitude);
+                    self.elapsed_secs += step * audio.boost(AudioParam::Rotation);
+                } else {
+                    self.elapsed_secs += step;
+                }
                 last_tick = Instant::now();
-                self.tick_count += 1;
             }
         }
-        Ok(())
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "cube --marker {} --tick-rate {} --x-rotation-speed {} --y-rotation-speed {} --z-rotation-speed {} --amplitude {} --frequency {} --speed {} --fov {} --fov-step {} --eye-separation {}",
+            self.marker,
+            self.tick_rate,
+            self.x_rotation_speed,
+            self.y_rotation_speed,
+            self.z_rotation_speed,
+            self.amplitude,
+            self.frequency,
+            self.speed,
+            self.val,
+            self.fov_step,
+            self.eye_separation
+        );
+        if self.orthographic {
+            args += " --orthographic";
+        }
+        if let Some(color_speed) = self.color_speed {
+            args += &format!(" --color-speed {color_speed}");
+        }
+        if self.anaglyph {
+            args += " --anaglyph";
+        }
+        if self.thickness > 1 {
+            args += &format!(" --thickness {}", self.thickness);
+        }
+        if self.z_sort {
+            args += " --z-sort";
+        }
+        if self.solid {
+            args += " --solid";
+        }
+        if self.wireframe {
+            args += " --wireframe";
+        }
+        if self.skip_unchanged {
+            args += " --skip-unchanged";
+        }
+        args += &format!(" --resolution {}", self.resolution);
+        args
+    }
+
+    fn adjust_fov(&mut self, delta: f64) {
+        self.val = (self.val + delta).clamp(FOV_MIN, FOV_MAX);
+        self.debug_text = format!("FOV: {:.3}", self.val);
+    }
+
+    fn adjust_wave(&mut self, amplitude_delta: f64, frequency_delta: f64) {
+        self.amplitude = (self.amplitude + amplitude_delta).clamp(0.0, AMPLITUDE_MAX);
+        self.frequency = (self.frequency + frequency_delta).clamp(0.0, FREQUENCY_MAX);
+        self.debug_text = format!(
+            "amplitude: {:.1} frequency: {:.1}",
+            self.amplitude, self.frequency
+        );
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.exit = true,
+            // zooming in means a smaller fov
+            Action::ZoomIn => self.adjust_fov(-self.fov_step),
+            Action::ZoomOut => self.adjust_fov(self.fov_step),
+            Action::PanUp => self.adjust_wave(WAVE_STEP, 0.0),
+            Action::PanDown => self.adjust_wave(-WAVE_STEP, 0.0),
+            Action::PanLeft => self.adjust_wave(0.0, -WAVE_STEP),
+            Action::PanRight => self.adjust_wave(0.0, WAVE_STEP),
+            Action::Reset => {
+                self.amplitude = self.launch_amplitude;
+                self.frequency = self.launch_frequency;
+                self.adjust_wave(0.0, 0.0);
+            }
+            _ => (),
+        }
     }
 
     fn handle_key_press(&mut self, key: event::KeyEvent) {
+        if let Some(action) = input::map_key(key) {
+            self.apply(action);
+            return;
+        }
         if key.kind != KeyEventKind::Press {
             return;
         }
         match key.code {
-            KeyCode::Char('a') => self.val += 0.001,
-            KeyCode::Char('d') => self.val -= 0.001,
-            _ => {
-                if is_quit_key(key) {
-                    self.exit = true;
-                }
-            }
+            KeyCode::Char('j') => self.apply(Action::PanDown),
+            KeyCode::Char('k') => self.apply(Action::PanUp),
+            KeyCode::Char('h') => self.apply(Action::PanLeft),
+            KeyCode::Char('l') => self.apply(Action::PanRight),
+            _ => (),
         }
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+    fn draw(&self, frame: &mut Frame, scene: &Scene) {
+        frame.render_widget(self.canvas(scene), frame.area());
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
     }
 
-    fn canvas(&self) -> impl Widget + '_ {
-        Canvas::default()
-            .marker(self.marker)
-            .paint(|ctx| {
-                let t = self.tick_count as f64 * 0.01;
-                let mut c: u16 = 0;
-                for win in self.points.windows(2) {
-                    let mut line_points: [DVec2; 2] = [DVec2::ZERO; 2];
-                    for (i, point) in win.iter().enumerate() {
-                        let mut point = *point;
-                        point.y += self.amplitude
-                            * (self.frequency * point.z + 20.0 * self.speed * t).sin();
-
-                        let mut modified_point = rotate_x(point, t * self.x_rotation_speed);
-                        modified_point = rotate_y(modified_point, t * self.y_rotation_speed);
-                        modified_point = rotate_z(modified_point, t * self.z_rotation_speed);
-
-                        if self.orthographic {
-                            line_points[i] =
-                                modified_point.to_screen_position_orthographic(self.playground);
-                        } else {
-                            modified_point += 30.0 * DVec3::Z;
-                            line_points[i] =
-                                modified_point.to_screen_position(self.playground, self.val);
-                        }
-                    }
+    fn project(&self, point: DVec3) -> DVec2 {
+        if self.orthographic {
+            point.to_screen_position_orthographic(self.playground, 3.0)
+        } else {
+            (point + 30.0 * DVec3::Z).to_screen_position(self.playground, self.val)
+        }
+    }
 
-                    let p0 = line_points[0];
-                    let p1 = line_points[1];
-                    let original_p0 = win[0];
-                    let original_p1 = win[1];
+    /// wave and rotate a point of the grid to where it is at time `t`
+    fn transform(&self, point: DVec3, t: f64) -> DVec3 {
+        let mut point = point;
+        point.y += self.amplitude
+            * self.amplitude_boost
+            * (self.frequency * point.z + 20.0 * self.speed * t).sin();
+        let mut modified_point = rotate_x(point, t * self.x_rotation_speed);
+        modified_point = rotate_y(modified_point, t * self.y_rotation_speed);
+        rotate_z(modified_point, t * self.z_rotation_speed)
+    }
 
-                    if original_p0.distance(original_p1) > 2.0 {
-                        c += 1;
-                        continue;
-                    }
-                    let mut color = c.rem_euclid(15) as u8 + 1;
-                    if let Some(color_speed) = self.color_speed {
-                        color += ((t * 18.0 * color_speed) as u64).rem_euclid(256) as u8;
-                        color = ((color as u16).rem_euclid(256 - 16) + 16) as u8;
-                    }
-                    let line = Line::new(p0.x, p0.y, p1.x, p1.y, Color::Indexed(color));
-                    ctx.draw(&line);
+    /// flat shaded outer faces, farthest first, skipping faces turned away from the camera
+    fn project_faces(&self, scene: &mut Scene, t: f64) {
+        let light = DVec3::new(-0.5, 0.6, -0.6).normalize();
+        let points: Vec<DVec3> = self.points.iter().map(|p| self.transform(*p, t)).collect();
+        let mut faces = Vec::with_capacity(self.faces.len());
+        for quad in self.faces.iter() {
+            let [a, b, c, d] = quad.map(|i| points[i]);
+            let center = (a + b + c + d) * 0.25;
+            let mut normal = (c - a).cross(d - b).normalize_or_zero();
+            // the grid is centered on the origin, so outward points away from it
+            if normal.dot(center) < 0.0 {
+                normal = -normal;
+            }
+            let view = if self.orthographic {
+                DVec3::Z
+            } else {
+                center + 30.0 * DVec3::Z
+            };
+            if normal.dot(view) >= 0.0 {
+                continue;
+            }
+            let shade = 0.2 + 0.8 * normal.dot(light).max(0.0);
+            faces.push((center.z, [a, b, c, d], shade));
+        }
+        faces.sort_by(|x, y| y.0.total_cmp(&x.0));
+        for (_, [a, b, c, d], shade) in faces {
+            // grayscale ramp 232..=255
+            let color = Color::Indexed(232 + (23.0 * shade) as u8);
+            scene
+                .quads
+                .push(([a, b, c, d].map(|p| self.project(p)), color));
+        }
+    }
+
+    /// the cube at the current time projected to the canvas
+    fn scene(&self) -> Scene {
+        let t = self.elapsed_secs * 1.25;
+        let mut scene = Scene::default();
+        if self.solid {
+            self.project_faces(&mut scene, t);
+            return scene;
+        }
+        let mut c: u16 = 0;
+        let mut segments = Vec::with_capacity(self.points.len());
+        for (i, win) in self.points.windows(2).enumerate() {
+            // the step from the end of one line to the start of the next
+            if (i + 1) % (self.resolution + 1) == 0 {
+                c += 1;
+                continue;
+            }
+            let rotated_points = [self.transform(win[0], t), self.transform(win[1], t)];
+            let mut color = c.rem_euclid(15) as u8 + 1;
+            if let Some(color_speed) = self.color_speed {
+                color += ((t * 18.0 * color_speed) as u64).rem_euclid(256) as u8;
+                color = ((color as u16).rem_euclid(256 - 16) + 16) as u8;
+            }
+            segments.push((rotated_points[0], rotated_points[1], color));
+        }
+        if self.z_sort {
+            sort_back_to_front(&mut segments);
+        }
+        for (p0, p1, color) in segments {
+            if self.anaglyph {
+                // left eye in red, right eye in cyan
+                let offset = 0.5 * self.eye_separation * DVec3::X;
+                for (offset, color) in [(offset, Color::Red), (-offset, Color::Cyan)] {
+                    let s0 = self.project(p0 + offset);
+                    let s1 = self.project(p1 + offset);
+                    scene.lines.push((s0, s1, color));
                 }
-            })
+            } else {
+                let s0 = self.project(p0);
+                let s1 = self.project(p1);
+                scene.lines.push((s0, s1, Color::Indexed(color)));
+            }
+        }
+        scene
+    }
+
+    fn canvas<'a>(&'a self, scene: &'a Scene) -> impl Widget + 'a {
+        Canvas::default()
+            .marker(self.marker)
+            .paint(|ctx| scene.paint(ctx, self.thickness))
             .x_bounds([
                 self.playground.left() as f64,
                 self.playground.right() as f64,
@@ -0,0 +1,76 @@
+//! Gamepad input for setups without a keyboard, behind the `gamepad` feature.
+//!
+//! The pad produces the same [`Action`]s as the keyboard, so bindings and toys don't care
+//! where an action came from. Nothing changes without the feature or a connected pad:
+//! - south button: reset, north: next seed, west: previous seed
+//! - east: step, start: pause, select: palette
+//! - triggers: zoom out and in
+//! - left stick and d-pad: pan, right stick: turn
+
+use crate::input::Action;
+
+/// actions from the gamepad since the last call, call once per frame.
+/// Sticks pushed past the middle repeat their action on every call like a held key
+#[cfg(feature = "gamepad")]
+pub fn poll_actions() -> Vec<Action> {
+    use gilrs::{Axis, Button, EventType, Gilrs};
+    use std::cell::RefCell;
+
+    // how far a stick has to be pushed before it counts
+    const STICK_THRESHOLD: f32 = 0.5;
+
+    thread_local! {
+        // None if gilrs can't start, e.g. without access to the input devices
+        static GILRS: RefCell<Option<Gilrs>> = RefCell::new(Gilrs::new().ok());
+    }
+
+    let button_action = |button| match button {
+        Button::South => Some(Action::Reset),
+        Button::North => Some(Action::NextSeed),
+        Button::West => Some(Action::PrevSeed),
+        Button::East => Some(Action::Step),
+        Button::Start => Some(Action::Pause),
+        Button::Select => Some(Action::TogglePalette),
+        Button::LeftTrigger | Button::LeftTrigger2 => Some(Action::ZoomOut),
+        Button::RightTrigger | Button::RightTrigger2 => Some(Action::ZoomIn),
+        Button::DPadUp => Some(Action::PanUp),
+        Button::DPadDown => Some(Action::PanDown),
+        Button::DPadLeft => Some(Action::PanLeft),
+        Button::DPadRight => Some(Action::PanRight),
+        _ => None,
+    };
+    // (axis, action when pushed negative, action when pushed positive), stick y points up
+    let stick_actions = [
+        (Axis::LeftStickX, Action::PanLeft, Action::PanRight),
+        (Axis::LeftStickY, Action::PanDown, Action::PanUp),
+        (Axis::RightStickX, Action::TurnLeft, Action::TurnRight),
+    ];
+
+    GILRS.with_borrow_mut(|gilrs| {
+        let Some(gilrs) = gilrs else {
+            return Vec::new();
+        };
+        let mut actions = Vec::new();
+        while let Some(event) = gilrs.next_event() {
+            if let EventType::ButtonPressed(button, _) = event.event {
+                actions.extend(button_action(button));
+            }
+        }
+        for (_, gamepad) in gilrs.gamepads() {
+            for (axis, negative, positive) in stick_actions {
+                let value = gamepad.value(axis);
+                if value <= -STICK_THRESHOLD {
+                    actions.push(negative);
+                } else if value >= STICK_THRESHOLD {
+                    actions.push(positive);
+                }
+            }
+        }
+        actions
+    })
+}
+
+#[cfg(not(feature = "gamepad"))]
+pub fn poll_actions() -> Vec<Action> {
+    Vec::new()
+}
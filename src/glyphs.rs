@@ -0,0 +1,141 @@
+//! Character sets for the text drawing path.
+//!
+//! A [`GlyphSet`] gives an intensity ramp from empty to full for the `--ascii` modes and
+//! random characters for toys that scatter glyphs around. Picked with the global `--charset`.
+
+use oorandom::Rand32;
+use std::fmt;
+use std::str::FromStr;
+
+// from empty to full, every set starts with a space so empty cells stay blank
+const ASCII_RAMP: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+// halfwidth so every character still takes one cell, roughly by ink
+const KATAKANA_RAMP: [char; 10] = [' ', '･', 'ｰ', 'ｼ', 'ﾉ', 'ﾂ', 'ﾐ', 'ﾊ', 'ﾈ', 'ﾒ'];
+const BINARY_RAMP: [char; 3] = [' ', '0', '1'];
+// one more dot per step
+const BRAILLE_RAMP: [char; 9] = [' ', '⠁', '⠃', '⠇', '⡇', '⡏', '⡟', '⡿', '⣿'];
+// halfwidth ｦ to ﾝ
+const KATAKANA_RANGE: (u32, u32) = (0xff66, 0xff9d);
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum GlyphSet {
+    #[default]
+    Ascii,
+    Katakana,
+    Binary,
+    Braille,
+    /// the characters as given, from empty to full. Never empty and never one of the names
+    /// above, parsing "ascii" gives [`GlyphSet::Ascii`], so a custom set can't spell one
+    Custom(Vec<char>),
+}
+
+impl GlyphSet {
+    /// characters from empty to full
+    pub fn ramp(&self) -> &[char] {
+        match self {
+            GlyphSet::Ascii => &ASCII_RAMP,
+            GlyphSet::Katakana => &KATAKANA_RAMP,
+            GlyphSet::Binary => &BINARY_RAMP,
+            GlyphSet::Braille => &BRAILLE_RAMP,
+            GlyphSet::Custom(chars) => chars,
+        }
+    }
+
+    /// ramp character for a level between 0 and 1
+    pub fn intensity_to_char(&self, level: f64) -> char {
+        let ramp = self.ramp();
+        let i = (level.clamp(0.0, 1.0) * (ramp.len() - 1) as f64).round() as usize;
+        ramp[i]
+    }
+
+    /// any character of the set but the blank one, katakana picks from the whole block
+    pub fn random(&self, rng: &mut Rand32) -> char {
+        if *self == GlyphSet::Katakana {
+            let (first, last) = KATAKANA_RANGE;
+            return char::from_u32(rng.rand_range(first..last + 1)).unwrap_or('ｱ');
+        }
+        let glyphs = match self.ramp() {
+            [' ', rest @ ..] if !rest.is_empty() => rest,
+            ramp => ramp,
+        };
+        glyphs[rng.rand_range(0..glyphs.len() as u32) as usize]
+    }
+}
+
+/// "ascii", "katakana", "binary", "braille" or the characters of a custom ramp
+impl FromStr for GlyphSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "ascii" => GlyphSet::Ascii,
+            "katakana" => GlyphSet::Katakana,
+            "binary" => GlyphSet::Binary,
+            "braille" => GlyphSet::Braille,
+            "" => return Err("the character set can't be empty".to_string()),
+            custom => GlyphSet::Custom(custom.chars().collect()),
+        })
+    }
+}
+
+/// the name it was parsed from
+impl fmt::Display for GlyphSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GlyphSet::Ascii => write!(f, "ascii"),
+            GlyphSet::Katakana => write!(f, "katakana"),
+            GlyphSet::Binary => write!(f, "binary"),
+            GlyphSet::Braille => write!(f, "braille"),
+            GlyphSet::Custom(chars) => write!(f, "{}", chars.iter().collect::<String>()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_sets_have_glyphs() {
+        for set in [
+            GlyphSet::Ascii,
+            GlyphSet::Katakana,
+            GlyphSet::Binary,
+            GlyphSet::Braille,
+        ] {
+            assert!(set.ramp().len() > 1, "{set}");
+            assert_eq!(set.to_string().parse(), Ok(set));
+        }
+    }
+
+    #[test]
+    fn custom_sets_round_trip() {
+        for chars in [" .oO@", "'", "ab", "asciii"] {
+            let set: GlyphSet = chars.parse().unwrap();
+            assert_eq!(set, GlyphSet::Custom(chars.chars().collect()));
+            assert_eq!(set.to_string(), chars);
+        }
+        assert_eq!("ascii".parse(), Ok(GlyphSet::Ascii));
+        assert!("".parse::<GlyphSet>().is_err());
+    }
+
+    #[test]
+    fn random_glyphs_come_from_the_set() {
+        let mut rng = Rand32::new(7);
+        for set in [GlyphSet::Ascii, GlyphSet::Binary, GlyphSet::Braille] {
+            for _ in 0..100 {
+                let c = set.random(&mut rng);
+                assert_ne!(c, ' ');
+                assert!(set.ramp().contains(&c), "{c} not in {set}");
+            }
+        }
+        let (first, last) = KATAKANA_RANGE;
+        for _ in 0..100 {
+            let c = GlyphSet::Katakana.random(&mut rng) as u32;
+            assert!((first..=last).contains(&c));
+        }
+        // a custom set of only a blank still has something to give
+        let blank: GlyphSet = " ".parse().unwrap();
+        assert_eq!(blank.random(&mut rng), ' ');
+    }
+}
@@ -0,0 +1,147 @@
+//! Rectangular grid of cells shared by the grid toys (life, sand, rain).
+//!
+//! Cells are indexed with `grid[(x, y)]`, y = 0 is the first row.
+
+use crate::utils::{calculate_hash, wrap_coord, Topology};
+use std::hash::Hash;
+use std::ops::{Index, IndexMut};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CellGrid<T> {
+    // row by row
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T: Clone> CellGrid<T> {
+    pub fn new(width: usize, height: usize, default: T) -> Self {
+        Self {
+            cells: vec![default; width * height],
+            width,
+            height,
+        }
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.cells.fill(value);
+    }
+}
+
+impl<T> CellGrid<T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// (width, height) in cells
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// true if there are no cells at all
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// cell at (x, y), None past the edges
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        (x < self.width && y < self.height).then(|| &self.cells[y * self.width + x])
+    }
+
+    /// cell at a possibly out of range position, mapped back onto the grid by `edges`.
+    /// None if it falls off a bounded grid
+    pub fn get_wrapped(&self, x: i32, y: i32, edges: Topology) -> Option<&T> {
+        let x = wrap_coord(x, self.width as i32, edges)?;
+        let y = wrap_coord(y, self.height as i32, edges)?;
+        Some(&self[(x, y)])
+    }
+
+    pub fn get_wrapped_mut(&mut self, x: i32, y: i32, edges: Topology) -> Option<&mut T> {
+        let x = wrap_coord(x, self.width as i32, edges)?;
+        let y = wrap_coord(y, self.height as i32, edges)?;
+        Some(&mut self[(x, y)])
+    }
+
+    /// every cell, row by row
+    pub fn cells(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    /// every cell with its (x, y), row by row
+    pub fn iter_cells(&self) -> impl Iterator<Item = (usize, usize, &T)> {
+        let width = self.width.max(1);
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, cell)| (i % width, i / width, cell))
+    }
+
+    /// the cells of row `y`, left to right
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    /// turn the grid upside down
+    pub fn flip_vertical(&mut self) {
+        for y in 0..self.height / 2 {
+            let (top, bottom) = self.cells.split_at_mut((self.height - 1 - y) * self.width);
+            top[y * self.width..(y + 1) * self.width].swap_with_slice(&mut bottom[..self.width]);
+        }
+    }
+}
+
+impl<T: Hash> CellGrid<T> {
+    /// changes whenever any cell does, for spotting boards that stopped changing
+    pub fn hash(&self) -> u64 {
+        calculate_hash(&self.cells)
+    }
+}
+
+impl<T> Index<(usize, usize)> for CellGrid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(
+            x < self.width,
+            "x {x} out of range for width {}",
+            self.width
+        );
+        &self.cells[y * self.width + x]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for CellGrid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(
+            x < self.width,
+            "x {x} out of range for width {}",
+            self.width
+        );
+        &mut self.cells[y * self.width + x]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flip_vertical_reverses_rows() {
+        for height in 1..6 {
+            let mut grid = CellGrid::new(3, height, 0);
+            for y in 0..height {
+                for x in 0..3 {
+                    grid[(x, y)] = y * 3 + x;
+                }
+            }
+            grid.flip_vertical();
+            for (x, y, &cell) in grid.iter_cells() {
+                assert_eq!(cell, (height - 1 - y) * 3 + x);
+            }
+        }
+    }
+}
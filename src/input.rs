@@ -0,0 +1,268 @@
+//! Shared key and mouse bindings.
+//!
+//! Toys that go through this layer only see [`Action`]s, so the bindings live in one place
+//! and the same keys do the same thing everywhere. Keys a toy doesn't act on are ignored by
+//! its `apply`. The `[keys]` table of the config file adds bindings on top of the defaults.
+
+use crate::utils::is_quit_key;
+use color_eyre::eyre::{eyre, WrapErr};
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Pause,
+    /// advance one tick and pause
+    Step,
+    Reset,
+    NextSeed,
+    PrevSeed,
+    ZoomIn,
+    ZoomOut,
+    TogglePalette,
+    // no default keys, for the gamepad sticks and the config file
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    TurnLeft,
+    TurnRight,
+}
+
+/// action bound to `key`, None for releases and unbound keys
+pub fn map_key(key: KeyEvent) -> Option<Action> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+    if let Some(&action) = BINDINGS
+        .get()
+        .and_then(|bindings| bindings.get(&normalize(key)))
+    {
+        return Some(action);
+    }
+    // before the plain keys so ctrl-c quits instead of toggling the palette
+    if is_quit_key(key) {
+        return Some(Action::Quit);
+    }
+    match key.code {
+        KeyCode::Char(' ') => Some(Action::Pause),
+        KeyCode::Char('f') => Some(Action::Step),
+        KeyCode::Char('r') => Some(Action::Reset),
+        KeyCode::Char('n') => Some(Action::NextSeed),
+        KeyCode::Char('N') => Some(Action::PrevSeed),
+        KeyCode::Char('d') => Some(Action::ZoomIn),
+        KeyCode::Char('a') => Some(Action::ZoomOut),
+        KeyCode::Char('c') => Some(Action::TogglePalette),
+        _ => None,
+    }
+}
+
+/// action bound to a mouse event, only the scroll wheel for now.
+/// Toys have to enable mouse capture to get these
+pub fn map_mouse(mouse: MouseEvent) -> Option<Action> {
+    match mouse.kind {
+        MouseEventKind::ScrollUp => Some(Action::ZoomIn),
+        MouseEventKind::ScrollDown => Some(Action::ZoomOut),
+        _ => None,
+    }
+}
+
+// names for the config file
+const ACTION_NAMES: [(&str, Action); 15] = [
+    ("quit", Action::Quit),
+    ("pause", Action::Pause),
+    ("step", Action::Step),
+    ("reset", Action::Reset),
+    ("next_seed", Action::NextSeed),
+    ("prev_seed", Action::PrevSeed),
+    ("zoom_in", Action::ZoomIn),
+    ("zoom_out", Action::ZoomOut),
+    ("toggle_palette", Action::TogglePalette),
+    ("pan_up", Action::PanUp),
+    ("pan_down", Action::PanDown),
+    ("pan_left", Action::PanLeft),
+    ("pan_right", Action::PanRight),
+    ("turn_left", Action::TurnLeft),
+    ("turn_right", Action::TurnRight),
+];
+
+// bindings from the config file, looked up before the defaults
+static BINDINGS: OnceLock<HashMap<KeyEvent, Action>> = OnceLock::new();
+
+/// read the `[keys]` table of the config file, e.g. `pause = "p"` or
+/// `zoom_in = ["d", "ctrl+up"]`. Without a path the default location is used if it exists
+pub fn load_key_bindings(path: Option<&Path>) -> Result<()> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => match default_config_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(()),
+        },
+    };
+    let text = std::fs::read_to_string(&path)
+        .wrap_err_with(|| format!("could not read {}", path.display()))?;
+    let bindings =
+        parse_bindings(&text).wrap_err_with(|| format!("could not load {}", path.display()))?;
+    // only called once at startup
+    let _ = BINDINGS.set(bindings);
+    Ok(())
+}
+
+// bindings from the `[keys]` table of a config file
+fn parse_bindings(text: &str) -> Result<HashMap<KeyEvent, Action>> {
+    let config: toml::Table = text.parse()?;
+    let mut bindings = HashMap::new();
+    let Some(keys) = config.get("keys") else {
+        return Ok(bindings);
+    };
+    let keys = keys
+        .as_table()
+        .ok_or_else(|| eyre!("[keys] is not a table"))?;
+
+    for (name, value) in keys {
+        let action = ACTION_NAMES
+            .iter()
+            .find(|(action_name, _)| action_name == name)
+            .map(|&(_, action)| action)
+            .ok_or_else(|| {
+                let names: Vec<_> = ACTION_NAMES.iter().map(|(name, _)| *name).collect();
+                eyre!(
+                    "unknown action '{name}' in [keys], expected one of: {}",
+                    names.join(", ")
+                )
+            })?;
+        let key_names = match value {
+            toml::Value::String(key) => vec![key.as_str()],
+            toml::Value::Array(keys) => keys
+                .iter()
+                .map(|key| key.as_str())
+                .collect::<Option<_>>()
+                .unwrap_or_default(),
+            _ => vec![],
+        };
+        if key_names.is_empty() {
+            return Err(eyre!("{name} in [keys] needs a key or a list of keys"));
+        }
+        for key_name in key_names {
+            let key =
+                parse_key(key_name).ok_or_else(|| eyre!("unknown key '{key_name}' for {name}"))?;
+            bindings.insert(key, action);
+        }
+    }
+    Ok(bindings)
+}
+
+// $XDG_CONFIG_HOME/terminal-toys/config.toml, or under ~/.config
+fn default_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("terminal-toys").join("config.toml"))
+}
+
+/// key from a name like "p", "N", "backspace" or "ctrl+alt+x", case doesn't matter for
+/// names and modifiers
+fn parse_key(s: &str) -> Option<KeyEvent> {
+    let mut chars = s.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(normalize(KeyEvent::new(
+            KeyCode::Char(c),
+            KeyModifiers::NONE,
+        )));
+    }
+    let mut parts: Vec<&str> = s.split('+').collect();
+    let name = parts.pop()?;
+    let mut modifiers = KeyModifiers::NONE;
+    for part in parts {
+        modifiers |= match part.to_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match name.to_lowercase().as_str() {
+            "backspace" => KeyCode::Backspace,
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "space" => KeyCode::Char(' '),
+            "plus" => KeyCode::Char('+'),
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+        },
+    };
+    Some(normalize(KeyEvent::new(code, modifiers)))
+}
+
+// same event for a key however the terminal reports it: press only, and shift folded into
+// the character since some terminals send 'N' with shift and some without
+fn normalize(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::SHIFT) => KeyEvent::new(
+            KeyCode::Char(c.to_ascii_uppercase()),
+            key.modifiers - KeyModifiers::SHIFT,
+        ),
+        code => KeyEvent::new(code, key.modifiers),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bindings() {
+        let bindings = parse_bindings(
+            "[keys]\npause = \"p\"\nstep = [\"ctrl+f\", \"F5\"]\nprev_seed = \"shift+n\"\n",
+        )
+        .unwrap();
+        let press = |code, modifiers| bindings.get(&normalize(KeyEvent::new(code, modifiers)));
+        assert_eq!(
+            press(KeyCode::Char('p'), KeyModifiers::NONE),
+            Some(&Action::Pause)
+        );
+        assert_eq!(
+            press(KeyCode::Char('f'), KeyModifiers::CONTROL),
+            Some(&Action::Step)
+        );
+        assert_eq!(
+            press(KeyCode::F(5), KeyModifiers::NONE),
+            Some(&Action::Step)
+        );
+        // some terminals report N with shift, some without
+        assert_eq!(
+            press(KeyCode::Char('N'), KeyModifiers::SHIFT),
+            Some(&Action::PrevSeed)
+        );
+        assert_eq!(
+            press(KeyCode::Char('N'), KeyModifiers::NONE),
+            Some(&Action::PrevSeed)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert!(parse_bindings("[keys]\nfly = \"p\"").is_err());
+        assert!(parse_bindings("[keys]\npause = \"hyper+p\"").is_err());
+        assert!(parse_bindings("[keys]\npause = []").is_err());
+        assert!(parse_bindings("[other]\nx = 1").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+//! terminal-toys is a collection of screensavers for the terminal.
+//!
+//! Each toy lives in its own module and exposes an `App` that can be built with
+//! `App::new` and driven with `App::run` on an existing [`ratatui::DefaultTerminal`].
+
+// every type in a public signature has to be exported too
+#![warn(unnameable_types, private_interfaces, private_bounds)]
+
+pub mod ant;
+pub mod balls;
+pub mod boids;
+pub mod bubble;
+mod cli;
+pub mod cube;
+mod gamepad;
+mod glyphs;
+pub mod grid;
+mod input;
+pub mod life;
+pub mod maze;
+pub mod palette;
+mod particle_grid;
+pub mod pipes2d;
+pub mod pipes3d;
+pub mod rain;
+pub mod rings;
+pub mod sand;
+#[cfg(test)]
+mod snapshot;
+pub mod splits;
+pub mod stars;
+pub mod tunnel;
+mod utils;
+
+pub use cli::{Cli, Commands, InfoFormat};
+pub use glyphs::GlyphSet;
+pub use input::load_key_bindings;
+pub use utils::{supports_truecolor, AudioLevel, AudioParam, BeatFifo, Mirror, Topology};
+pub use utils::{Backoff, FrameDumper, RunOptions};
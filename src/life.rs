@@ -1,69 +1,149 @@
-use crate::utils::{calc_board_size_stretched, calculate_hash, is_quit_key, map_range};
+use crate::gamepad;
+use crate::glyphs::GlyphSet;
+use crate::grid::CellGrid;
+use crate::input::{self, Action};
+use crate::utils::{
+    calc_board_size_stretched, draw_ascii, draw_palette_swatch, indexed_to_rgb, map_range,
+    next_seed, value_enum_name, Backoff, RunOptions, Topology,
+};
+use clap::ValueEnum;
 use color_eyre::Result;
-use crossterm::event::KeyEventKind;
+use crossterm::event::{
+    DisableMouseCapture, EnableMouseCapture, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::execute;
 use glam::DVec2;
 use oorandom::Rand64;
 use ratatui::{
+    backend::Backend,
     crossterm::event::{self, Event, KeyCode},
+    layout::{Constraint, Layout},
     style::Color,
     symbols::Marker,
     widgets::{
         canvas::{Canvas, Points, Rectangle},
         Paragraph, Widget,
     },
-    DefaultTerminal, Frame,
+    DefaultTerminal, Frame, Terminal,
 };
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+// random picks per cell while seeding before giving up on finding a dead one
+const SEED_ATTEMPTS: usize = 100;
+// outline of dead cells with --show-grid
+const GRID_COLOR: Color = Color::Indexed(236);
+/// generations kept for stepping back without --history, off since every one costs a copy
+/// of the live cells
+pub const DEFAULT_HISTORY: usize = 0;
+
+/// (alive, color index)
+pub type CellState = (bool, u8);
+
+/// live cells of one generation as (x, y, color index), boards come from terminal sizes so
+/// the coordinates fit in a u16
+type Generation = Vec<(u16, u16, u8)>;
+
 pub struct App {
-    grid: Vec<Vec<(bool, u8)>>,
+    grid: CellGrid<CellState>,
     hash_history: Vec<u64>,
+    // earlier generations for stepping back with b, newest last
+    history: VecDeque<Generation>,
+    history_len: usize,
+    paused: bool,
+    // a step taken while paused whose frame hasn't gone to --dump-frames yet
+    unrecorded_step: bool,
+    // stop on a still life or blinker instead of resetting
+    freeze_on_stable: bool,
+    // period of the pattern the board froze on
+    stable_period: Option<u8>,
     exit: bool,
     playground: DVec2,
     debug_text: String,
+    quiet: bool,
+    // c shows the colors in use
+    show_palette: bool,
     marker: Marker,
     rng: Rand64,
     is_sim_running: bool,
     n_generated: usize,
     initial_n_alive: usize,
     pixel: bool,
+    n_colors: u8,
+    edges: Topology,
+    generation: u64,
+    show_hud: bool,
+    brush_size: usize,
+    terminal_size: (u16, u16),
+    ascii: bool,
+    // keep going after the board settles instead of resetting
+    no_reset: bool,
+    show_grid: bool,
+    loop_seed: bool,
+    tick_rate: u64,
+    seed: u128,
+    initial_percentage_alive: f32,
+    color_rule: ColorRule,
+}
+
+/// how a cell coming alive picks its color from its neighbors
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorRule {
+    /// First colored neighbor, starting from the bottom left
+    #[default]
+    First,
+    /// Most common color of the live neighbors, the first one on a tie
+    Majority,
+    /// Palette color closest to the mix of the live neighbors
+    Average,
 }
 
 impl App {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         terminal_width: u16,
         terminal_height: u16,
         marker: Marker,
         seed: u128,
         initial_percentage_alive: f32,
+        n_colors: u8,
         board_width: Option<usize>,
+        board_height: Option<usize>,
+        edges: Topology,
+        ascii: bool,
+        no_reset: bool,
+        show_grid: bool,
+        color_rule: ColorRule,
+        history_len: usize,
+        freeze_on_stable: bool,
     ) -> Self {
         let scale_factor = terminal_height as f32 / terminal_width as f32;
         let font_scale_factor = 2.0;
         let width = 200.0;
         let height = width * scale_factor * font_scale_factor;
         let wh_factor = height / width;
-        let pixel = board_width.is_none();
+        let pixel = board_width.is_none() && board_height.is_none();
         let rng = oorandom::Rand64::new(seed);
-        let mut grid = Vec::new();
 
-        let (board_width, board_height) = match board_width {
-            Some(width) => (width, (width as f32 * wh_factor) as usize),
-            None => calc_board_size_stretched(marker, terminal_width, terminal_height),
+        let (board_width, board_height) = match (board_width, board_height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, ((width as f32 * wh_factor) as usize).max(1)),
+            (None, Some(height)) => (((height as f32 / wh_factor) as usize).max(1), height),
+            (None, None) => calc_board_size_stretched(marker, terminal_width, terminal_height),
         };
 
-        let initial_n_alive =
-            (initial_percentage_alive * (board_width * board_height) as f32) as usize;
-
-        for _ in 0..board_height {
-            let mut line = Vec::new();
-            line.resize(board_width, (false, 0));
-            grid.push(line);
-        }
+        let n_cells = board_width * board_height;
+        let initial_n_alive = ((initial_percentage_alive * n_cells as f32) as usize).min(n_cells);
 
         Self {
-            grid,
+            grid: CellGrid::new(board_width, board_height, (false, 0)),
             hash_history: Vec::new(),
+            history: VecDeque::new(),
+            history_len,
+            paused: false,
+            unrecorded_step: false,
+            freeze_on_stable,
+            stable_period: None,
             exit: false,
             playground: DVec2::new(width as f64, height as f64),
             marker,
@@ -73,74 +153,393 @@ impl App {
             n_generated: 0,
             initial_n_alive,
             pixel,
+            n_colors,
+            edges,
+            generation: 0,
+            show_hud: false,
+            brush_size: 1,
+            terminal_size: (terminal_width, terminal_height),
+            ascii,
+            no_reset,
+            show_grid,
+            color_rule,
+            loop_seed: false,
+            quiet: false,
+            show_palette: false,
+            tick_rate: 0,
+            seed,
+            initial_percentage_alive,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        let tick_rate = Duration::from_millis(32);
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        self.loop_seed = options.loop_seed;
+        let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
+        execute!(std::io::stdout(), EnableMouseCapture)?;
         self.reset();
 
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            self.render(&mut terminal, &options, &mut backoff)?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
-                    Event::Resize(_columns, _rows) => {
-                        // self.debug_text = format!("{} {}", columns, rows);
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
+                    Event::Mouse(mouse) => self.handle_mouse(mouse),
+                    Event::Resize(columns, rows) => {
+                        self.terminal_size = (columns, rows);
                     }
                     _ => (),
                 }
             }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
 
             if last_tick.elapsed() >= tick_rate {
                 if self.is_sim_running {
-                    self.on_tick();
-                } else {
-                    let ticks_to_generate = 50;
-                    let n_to_generate_per_tick = self.initial_n_alive / ticks_to_generate;
-                    let n_to_generate = std::cmp::min(
-                        self.initial_n_alive - self.n_generated,
-                        n_to_generate_per_tick,
-                    );
-
-                    for _ in 0..n_to_generate {
-                        let x = self.rng.rand_range(0..self.grid[0].len() as u64) as usize;
-                        let y = self.rng.rand_range(0..self.grid.len() as u64) as usize;
-                        let color = self.rng.rand_range(1..13) as u8;
-                        self.grid[y][x] = (true, color);
-                        self.n_generated += 1;
-                        if self.n_generated >= self.initial_n_alive {
-                            self.is_sim_running = true;
-                        }
+                    if !self.paused && self.stable_period.is_none() {
+                        self.on_tick();
                     }
+                } else {
+                    self.seed_step();
+                }
+                if self.show_hud {
+                    self.update_hud();
                 }
                 last_tick = Instant::now();
             }
         }
-        Ok(())
+        execute!(std::io::stdout(), DisableMouseCapture)?;
+        Ok(self)
+    }
+
+    /// draw a frame, while paused only the first one after each step goes to --dump-frames
+    fn render<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        options: &RunOptions,
+        backoff: &mut Backoff,
+    ) -> Result<()>
+    where
+        B::Error: Send + Sync + 'static,
+    {
+        let record = !self.paused || std::mem::take(&mut self.unrecorded_step);
+        options.draw_recording(terminal, backoff, record, |frame| {
+            self.draw(frame, options.glyphs())
+        })
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "life --marker {} --tick-rate {} -n {} --n-colors {} --edges {} --seed {}",
+            self.marker,
+            self.tick_rate,
+            self.initial_percentage_alive,
+            self.n_colors,
+            value_enum_name(self.edges),
+            self.seed
+        );
+        if !self.pixel {
+            let (width, height) = self.dimensions();
+            args += &format!(" --width {width} --height {height}");
+        }
+        if self.ascii {
+            args += " --ascii";
+        }
+        if self.no_reset {
+            args += " --no-reset";
+        }
+        if self.show_grid {
+            args += " --show-grid";
+        }
+        if self.color_rule != ColorRule::First {
+            args += &format!(" --color-rule {}", value_enum_name(self.color_rule));
+        }
+        if self.history_len != DEFAULT_HISTORY {
+            args += &format!(" --history {}", self.history_len);
+        }
+        if self.freeze_on_stable {
+            args += " --freeze-on-stable";
+        }
+        args
+    }
+
+    /// line to print after quitting with --freeze-on-stable
+    pub fn summary(&self) -> Option<String> {
+        if !self.freeze_on_stable {
+            return None;
+        }
+        let state = match self.stable_period {
+            Some(1) => "still life".to_string(),
+            Some(period) => format!("period {period}"),
+            None => "not stable yet".to_string(),
+        };
+        Some(format!(
+            "life: {} generations, {} live cells, {state}",
+            self.generation,
+            self.live_count()
+        ))
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> Option<CellState> {
+        self.grid.get(x, y).copied()
+    }
+
+    /// (width, height) of the board in cells
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.grid.dimensions()
+    }
+
+    pub fn live_count(&self) -> usize {
+        self.grid.iter_cells().filter(|(_, _, c)| c.0).count()
+    }
+
+    /// scatter the next batch of live cells, the board fills up over 50 ticks before the
+    /// simulation starts
+    fn seed_step(&mut self) {
+        let ticks_to_generate = 50;
+        // rounded up so small counts still finish within ticks_to_generate
+        let n_to_generate_per_tick = self.initial_n_alive.div_ceil(ticks_to_generate);
+        let n_to_generate = std::cmp::min(
+            self.initial_n_alive - self.n_generated,
+            n_to_generate_per_tick,
+        );
+
+        // retry on live cells, but give up on a nearly full board
+        let mut n_placed = 0;
+        let mut attempts_left = n_to_generate * SEED_ATTEMPTS;
+        while n_placed < n_to_generate && attempts_left > 0 {
+            attempts_left -= 1;
+            let x = self.rng.rand_range(0..self.grid.width() as u64) as usize;
+            let y = self.rng.rand_range(0..self.grid.height() as u64) as usize;
+            if self.grid[(x, y)].0 {
+                continue;
+            }
+            let color = self.rng.rand_range(1..self.n_colors as u64 + 1) as u8;
+            self.grid[(x, y)] = (true, color);
+            n_placed += 1;
+        }
+        self.n_generated += n_placed;
+        if self.n_generated >= self.initial_n_alive || n_placed < n_to_generate {
+            self.is_sim_running = true;
+        }
+    }
+
+    fn update_hud(&mut self) {
+        self.debug_text = format!(
+            "gen {}{}\nlive {}\nB3/S23\nbrush {}\nseed {}",
+            self.generation,
+            if self.stable_period.is_some() {
+                " (stable)"
+            } else if self.paused {
+                " (paused)"
+            } else {
+                ""
+            },
+            self.live_count(),
+            self.brush_size,
+            self.seed
+        );
+    }
+
+    /// bring pattern cells to life centered on the board, (0, 0) is the top left of the pattern.
+    /// cells that don't fit are skipped, returns how many
+    pub fn stamp_pattern(&mut self, cells: &[(usize, usize)]) -> usize {
+        let (width, height) = self.dimensions();
+        let (Some(min_x), Some(min_y)) = (
+            cells.iter().map(|c| c.0).min(),
+            cells.iter().map(|c| c.1).min(),
+        ) else {
+            return 0;
+        };
+        let max_x = cells.iter().map(|c| c.0).max().unwrap_or(min_x);
+        let max_y = cells.iter().map(|c| c.1).max().unwrap_or(min_y);
+        let offset_x = (width as i64 - (max_x - min_x + 1) as i64) / 2;
+        let offset_y = (height as i64 - (max_y - min_y + 1) as i64) / 2;
+
+        let mut n_clipped = 0;
+        for &(x, y) in cells {
+            let board_x = offset_x + (x - min_x) as i64;
+            // board row 0 is the bottom
+            let board_y = height as i64 - 1 - (offset_y + (y - min_y) as i64);
+            if !(0..width as i64).contains(&board_x) || !(0..height as i64).contains(&board_y) {
+                n_clipped += 1;
+                continue;
+            }
+            let color = self.rng.rand_range(1..self.n_colors as u64 + 1) as u8;
+            self.grid[(board_x as usize, board_y as usize)] = (true, color);
+        }
+        if n_clipped > 0 {
+            self.debug_text = format!("pattern too large, clipped {n_clipped} cells");
+        }
+        n_clipped
+    }
+
+    /// reset on its own, starting from a new seed with --loop-seed
+    fn auto_reset(&mut self) {
+        if self.loop_seed {
+            self.seed = next_seed(&mut self.rng);
+            self.rng = oorandom::Rand64::new(self.seed);
+        }
+        self.reset();
+    }
+
+    /// start over from `seed`, n and N step through seeds
+    fn set_seed(&mut self, seed: u128) {
+        self.seed = seed;
+        self.rng = oorandom::Rand64::new(seed);
+        self.reset();
+        if self.show_hud {
+            self.update_hud();
+        } else {
+            self.debug_text = format!("seed: {seed}");
+        }
     }
 
     fn reset(&mut self) {
+        self.generation = 0;
         self.is_sim_running = false;
         self.n_generated = 0;
-        for line in self.grid.iter_mut() {
-            for val in line.iter_mut() {
-                *val = (false, 0);
+        self.grid.fill((false, 0));
+        self.history.clear();
+        self.paused = false;
+        self.stable_period = None;
+    }
+
+    /// go back to the previous recorded generation and pause there.
+    /// Life can't be run backwards, so this only replays what on_tick recorded
+    fn step_back(&mut self) {
+        let Some(cells) = self.history.pop_back() else {
+            self.debug_text = if self.history_len == 0 {
+                "start with --history N to step back".to_string()
+            } else {
+                "no earlier generations recorded".to_string()
+            };
+            return;
+        };
+        self.unrecorded_step = true;
+        self.grid.fill((false, 0));
+        let (width, height) = self.dimensions();
+        for (x, y, color) in cells {
+            let (x, y) = (x as usize, y as usize);
+            // the board may have shrunk since
+            if x < width && y < height {
+                self.grid[(x, y)] = (true, color);
+            }
+        }
+        self.generation = self.generation.saturating_sub(1);
+        // the cycle check only makes sense for generations in a row
+        self.hash_history.clear();
+        self.stable_period = None;
+        self.set_paused(true);
+    }
+
+    /// run one generation and pause there
+    fn step_forward(&mut self) {
+        self.set_paused(true);
+        self.unrecorded_step = true;
+        if self.is_sim_running && self.stable_period.is_none() {
+            self.on_tick();
+            if self.show_hud {
+                self.update_hud();
+            } else {
+                self.debug_text = format!("paused at gen {}", self.generation);
             }
         }
     }
 
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if self.show_hud {
+            self.update_hud();
+        } else if paused {
+            self.debug_text = format!("paused at gen {}", self.generation);
+        } else {
+            self.debug_text.clear();
+        }
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.exit = true,
+            Action::Pause => self.set_paused(!self.paused),
+            Action::Step => self.step_forward(),
+            Action::Reset => self.reset(),
+            Action::NextSeed => self.set_seed(self.seed.wrapping_add(1)),
+            Action::PrevSeed => self.set_seed(self.seed.wrapping_sub(1)),
+            Action::TogglePalette => self.show_palette = !self.show_palette,
+            _ => (),
+        }
+    }
+
+    /// keys only life has, everything else goes through the shared bindings
     fn handle_key_press(&mut self, key: event::KeyEvent) {
+        if let Some(action) = input::map_key(key) {
+            self.apply(action);
+            return;
+        }
         if key.kind != KeyEventKind::Press {
             return;
         }
         match key.code {
-            KeyCode::Char('r') => self.reset(),
-            _ => {
-                if is_quit_key(key) {
-                    self.exit = true;
+            KeyCode::Char('i') => {
+                self.show_hud = !self.show_hud;
+                if self.show_hud {
+                    self.update_hud();
+                } else {
+                    self.debug_text.clear();
+                }
+            }
+            KeyCode::Char('b') => self.step_back(),
+            KeyCode::Char('[') => self.brush_size = self.brush_size.saturating_sub(1).max(1),
+            KeyCode::Char(']') => self.brush_size = (self.brush_size + 1).min(20),
+            _ => (),
+        }
+    }
+
+    /// left button draws live cells, right button erases
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let alive =
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left)
+                | MouseEventKind::Drag(MouseButton::Left) => true,
+                MouseEventKind::Down(MouseButton::Right)
+                | MouseEventKind::Drag(MouseButton::Right) => false,
+                _ => return,
+            };
+        let (width, height) = self.dimensions();
+        let (columns, rows) = self.terminal_size;
+        if width == 0 || height == 0 || columns == 0 || rows == 0 {
+            return;
+        }
+        // canvas y goes up, terminal rows go down
+        let center_x = mouse.column as usize * width / columns as usize;
+        let center_y = (rows - 1).saturating_sub(mouse.row) as usize * height / rows as usize;
+        let radius = self.brush_size as i32 - 1;
+        for y_off in -radius..=radius {
+            for x_off in -radius..=radius {
+                let (x, y) = (center_x as i32 + x_off, center_y as i32 + y_off);
+                if let Some(cell) = self.grid.get_wrapped_mut(x, y, self.edges) {
+                    *cell = if alive {
+                        (true, self.rng.rand_range(1..self.n_colors as u64 + 1) as u8)
+                    } else {
+                        (false, 0)
+                    };
                 }
             }
         }
@@ -150,28 +549,46 @@ impl App {
         if self.grid.is_empty() {
             return;
         }
+        if self.history_len > 0 {
+            if self.history.len() == self.history_len {
+                self.history.pop_front();
+            }
+            let cells = self.grid.iter_cells();
+            let live = cells
+                .filter(|(_, _, c)| c.0)
+                .map(|(x, y, c)| (x as u16, y as u16, c.1));
+            self.history.push_back(live.collect());
+        }
         let mut new_grid = self.grid.clone();
-        let height = self.grid.len();
-        let width = self.grid[0].len();
+        let (width, height) = self.dimensions();
         #[allow(clippy::needless_range_loop)]
         for y in 0..height {
             for x in 0..width {
-                let (n_alive, color) = grid_neighbors(&self.grid, x, y);
+                let (n_alive, color) =
+                    grid_neighbors(&self.grid, x, y, self.n_colors, self.edges, self.color_rule);
                 #[allow(clippy::manual_range_contains)]
                 if n_alive < 2 || n_alive > 3 {
-                    new_grid[y][x] = (false, 0);
+                    new_grid[(x, y)] = (false, 0);
                 }
                 if n_alive == 3 {
-                    new_grid[y][x] = (true, color);
+                    new_grid[(x, y)] = (true, color);
                 }
             }
         }
         self.grid = new_grid;
-        let hash = calculate_hash(&self.grid);
+        self.generation += 1;
+        let hash = self.grid.hash();
         if self.hash_history.len() == 3 {
-            if self.hash_history[0] == self.hash_history[2] {
+            if self.hash_history[0] == self.hash_history[2] && self.freeze_on_stable {
+                // a still life repeats every generation, anything else caught here every second
+                let still = self.hash_history[1] == self.hash_history[2];
+                self.stable_period = Some(if still { 1 } else { 2 });
                 self.hash_history.clear();
-                self.reset();
+                return;
+            }
+            if self.hash_history[0] == self.hash_history[2] && !self.no_reset {
+                self.hash_history.clear();
+                self.auto_reset();
                 return;
             }
             self.hash_history.rotate_left(1);
@@ -180,11 +597,37 @@ impl App {
         self.hash_history.push(hash);
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+    fn draw(&self, frame: &mut Frame, glyphs: &GlyphSet) {
+        if self.ascii {
+            let area = frame.area();
+            let (width, height) = self.dimensions();
+            draw_ascii(frame.buffer_mut(), area, glyphs, width, height, |x, y| {
+                let (alive, color) = self.grid[(x, y)];
+                if alive {
+                    color as f64 / self.n_colors as f64
+                } else {
+                    0.0
+                }
+            });
+        } else {
+            frame.render_widget(self.canvas(), frame.area());
+        }
+        if self.show_palette && !self.quiet {
+            let area = frame.area();
+            draw_palette_swatch(frame.buffer_mut(), area, 1..=self.n_colors);
+        }
+        if !self.quiet && !self.debug_text.is_empty() {
+            // keep the text in the top left corner so it covers as little as possible
+            let text_width = self.debug_text.lines().map(|l| l.len()).max().unwrap_or(0);
+            let text_height = self.debug_text.lines().count();
+            let [area, _] =
+                Layout::vertical([Constraint::Length(text_height as u16), Constraint::Fill(1)])
+                    .areas(frame.area());
+            let [area, _] =
+                Layout::horizontal([Constraint::Length(text_width as u16), Constraint::Fill(1)])
+                    .areas(area);
             let debug_text = Paragraph::new(self.debug_text.clone());
-            frame.render_widget(debug_text, frame.area());
+            frame.render_widget(debug_text, area);
         }
     }
 
@@ -195,32 +638,45 @@ impl App {
                 if self.grid.is_empty() {
                     return;
                 }
-                let width = self.grid[0].len();
-                let height = self.grid.len();
+                let (width, height) = self.dimensions();
                 let square_width = self.playground.x / width as f64;
                 let square_height = self.playground.y / height as f64;
 
-                for (y, line) in self.grid.iter().enumerate() {
-                    let y = map_range(y as f64, 0.0, height as f64, 0.0, self.playground.y);
-                    for (x, &(val, color)) in line.iter().enumerate() {
+                // dead cells first, so live ones draw over the shared edges
+                if self.show_grid && !self.pixel {
+                    for (x, y, _) in self.grid.iter_cells().filter(|(_, _, cell)| !cell.0) {
+                        let y = map_range(y as f64, 0.0, height as f64, 0.0, self.playground.y);
                         let x = map_range(x as f64, 0.0, width as f64, 0.0, self.playground.x);
-                        if val {
-                            if self.pixel {
-                                ctx.draw(&Points {
-                                    coords: &[(x, y)],
-                                    color: Color::Indexed(color),
-                                });
-                            } else {
-                                let square = Rectangle {
-                                    x,
-                                    y,
-                                    width: square_width,
-                                    height: square_height,
-                                    color: Color::Indexed(color),
-                                };
-                                ctx.draw(&square);
-                            }
-                        }
+                        ctx.draw(&Rectangle {
+                            x,
+                            y,
+                            width: square_width,
+                            height: square_height,
+                            color: GRID_COLOR,
+                        });
+                    }
+                }
+
+                for (x, y, &(val, color)) in self.grid.iter_cells() {
+                    if !val {
+                        continue;
+                    }
+                    let y = map_range(y as f64, 0.0, height as f64, 0.0, self.playground.y);
+                    let x = map_range(x as f64, 0.0, width as f64, 0.0, self.playground.x);
+                    if self.pixel {
+                        ctx.draw(&Points {
+                            coords: &[(x, y)],
+                            color: Color::Indexed(color),
+                        });
+                    } else {
+                        let square = Rectangle {
+                            x,
+                            y,
+                            width: square_width,
+                            height: square_height,
+                            color: Color::Indexed(color),
+                        };
+                        ctx.draw(&square);
                     }
                 }
             })
@@ -229,14 +685,18 @@ impl App {
     }
 }
 
-fn grid_neighbors(grid: &[Vec<(bool, u8)>], x: usize, y: usize) -> (usize, u8) {
-    let height = grid.len() as i32;
-    if height == 0 {
-        return (0, 0);
-    }
-    let width = grid[0].len() as i32;
-    let mut color = 0;
+/// number of live neighbors and the color a cell born at (x, y) gets
+fn grid_neighbors(
+    grid: &CellGrid<CellState>,
+    x: usize,
+    y: usize,
+    n_colors: u8,
+    edges: Topology,
+    color_rule: ColorRule,
+) -> (usize, u8) {
+    let mut first_color = 0;
     let mut count = 0;
+    let mut live_colors = [0; 8];
     for (x_off, y_off) in [
         (-1, -1),
         (0, -1),
@@ -251,25 +711,182 @@ fn grid_neighbors(grid: &[Vec<(bool, u8)>], x: usize, y: usize) -> (usize, u8) {
     ]
     .iter()
     {
-        let mut x = x as i32 + x_off;
-        let mut y = y as i32 + y_off;
-        if x == -1 {
-            x = width - 1;
-        } else if x == width {
-            x = 0;
-        }
-        if y == -1 {
-            y = height - 1;
-        } else if y == height {
-            y = 0;
-        }
-        let item = grid[y as usize][x as usize];
+        let Some(&item) = grid.get_wrapped(x as i32 + x_off, y as i32 + y_off, edges) else {
+            continue;
+        };
         if item.0 {
+            live_colors[count] = item.1;
             count += 1;
         }
-        if color == 0 {
-            color = item.1;
+        if first_color == 0 {
+            first_color = item.1;
         }
     }
-    (count, color)
+    let live_colors = &live_colors[..count];
+    // only cells with 3 neighbors come alive, the others don't need the other rules
+    let color = match color_rule {
+        _ if count != 3 => first_color,
+        ColorRule::First => first_color,
+        ColorRule::Majority => majority_color(live_colors),
+        ColorRule::Average => average_color(live_colors, n_colors),
+    };
+    (count, color.clamp(1, n_colors))
+}
+
+/// most common of `colors`, the earliest one on a tie
+fn majority_color(colors: &[u8]) -> u8 {
+    let votes = |color| colors.iter().filter(|&&c| c == color).count();
+    let mut best = 0;
+    let mut best_votes = 0;
+    for &color in colors {
+        let n = votes(color);
+        if n > best_votes {
+            best = color;
+            best_votes = n;
+        }
+    }
+    best
+}
+
+/// palette color 1..=n_colors closest to the mix of `colors`, the lowest one on a tie
+fn average_color(colors: &[u8], n_colors: u8) -> u8 {
+    if colors.is_empty() {
+        return 0;
+    }
+    let mut sum = [0; 3];
+    for &color in colors {
+        let (r, g, b) = indexed_to_rgb(color);
+        sum[0] += r as i32;
+        sum[1] += g as i32;
+        sum[2] += b as i32;
+    }
+    let n = colors.len() as i32;
+    let (r, g, b) = (sum[0] / n, sum[1] / n, sum[2] / n);
+    (1..=n_colors)
+        .min_by_key(|&c| {
+            let (r2, g2, b2) = indexed_to_rgb(c);
+            (r - r2 as i32).pow(2) + (g - g2 as i32).pow(2) + (b - b2 as i32).pow(2)
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot;
+    use crate::utils::FrameDumper;
+    use ratatui::backend::TestBackend;
+
+    fn app(width: usize, height: usize, percentage_alive: f32) -> App {
+        App::new(
+            80,
+            24,
+            Marker::HalfBlock,
+            3,
+            percentage_alive,
+            12,
+            Some(width),
+            Some(height),
+            Topology::Torus,
+            false,
+            false,
+            false,
+            ColorRule::First,
+            DEFAULT_HISTORY,
+            false,
+        )
+    }
+
+    // seed like run does, None if seeding never finished
+    fn seed(app: &mut App) -> Option<usize> {
+        app.reset();
+        (1..=1000).find(|_| {
+            app.seed_step();
+            app.is_sim_running
+        })
+    }
+
+    // the board after seeding and a few generations
+    fn board(app: &App) -> String {
+        let (width, height) = app.dimensions();
+        let mut board = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                let (alive, color) = app.cell(x, y).unwrap();
+                board.push(snapshot::cell_char(alive.then_some(color), None));
+            }
+            board.push('\n');
+        }
+        board
+    }
+
+    #[test]
+    fn generations_match_snapshot() {
+        for edges in [Topology::Torus, Topology::Bounded, Topology::Mirror] {
+            let mut app = app(48, 24, 0.3);
+            app.edges = edges;
+            seed(&mut app);
+            for _ in 0..40 {
+                app.on_tick();
+            }
+            let name = format!("life_{}", value_enum_name(edges));
+            snapshot::assert_snapshot(&name, &board(&app));
+        }
+    }
+
+    #[test]
+    fn full_board_seeding_terminates() {
+        let mut app = app(40, 20, 1.0);
+        assert!(seed(&mut app).is_some());
+        // the last few dead cells are hard to hit, but most of the board fills up
+        assert!(app.live_count() > 40 * 20 * 9 / 10);
+    }
+
+    #[test]
+    fn seeding_reaches_requested_count() {
+        for percentage_alive in [0.1, 0.3, 0.5, 0.7] {
+            let mut app = app(60, 30, percentage_alive);
+            assert!(seed(&mut app).is_some());
+            let requested = app.initial_n_alive;
+            // collisions are retried, so only an unlucky streak can leave cells out
+            assert!(app.live_count() <= requested);
+            assert!(app.live_count() >= requested * 99 / 100);
+        }
+    }
+
+    #[test]
+    fn paused_span_dumps_no_frames() {
+        let dir = std::env::temp_dir().join(format!("terminal-toys-{}-life", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dumper = FrameDumper::new(dir.clone(), 100);
+        let options = RunOptions {
+            frame_dumper: Some(dumper.clone()),
+            ..Default::default()
+        };
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        let mut backoff = Backoff::new(false);
+        let mut app = app(20, 10, 30.0);
+        seed(&mut app).unwrap();
+        let mut render = |app: &mut App, frames| {
+            for _ in 0..frames {
+                app.render(&mut terminal, &options, &mut backoff).unwrap();
+            }
+            dumper.count()
+        };
+
+        assert_eq!(render(&mut app, 2), 2);
+        app.apply(Action::Pause);
+        assert_eq!(render(&mut app, 5), 2);
+        // one frame per step, however long it stays on screen
+        app.apply(Action::Step);
+        assert_eq!(render(&mut app, 5), 3);
+        // the generation in between was never drawn, so there's nothing to dump for it
+        app.apply(Action::Step);
+        app.apply(Action::Step);
+        assert_eq!(render(&mut app, 1), 4);
+        app.apply(Action::Pause);
+        assert_eq!(render(&mut app, 2), 6);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -1,307 +1,218 @@
-mod ant;
-mod balls;
-mod bubble;
-mod cube;
-mod life;
-mod pipes3d;
-mod rings;
-mod sand;
-mod splits;
-mod tunnel;
-mod utils;
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use crossterm::{cursor::MoveTo, execute};
+use ratatui::{backend::CrosstermBackend, DefaultTerminal, Terminal, TerminalOptions, Viewport};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use clap::{error::ErrorKind, parser::ValueSource, CommandFactory, FromArgMatches, Parser};
+use terminal_toys::{
+    ant, balls, boids, bubble, cube, life, load_key_bindings, maze, pipes2d, pipes3d, rain, rings,
+    sand, splits, stars, supports_truecolor, tunnel, AudioLevel, AudioParam, BeatFifo, Cli,
+    Commands, FrameDumper, InfoFormat, RunOptions,
+};
 
-use color_eyre::Result;
-
-use clap::{Parser, Subcommand};
-use ratatui::symbols::Marker;
-
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Cli {
-    #[command(subcommand)]
-    command: Commands,
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+    // clap only catches the conflict when --fps comes after the subcommand
+    if let Some((_, sub_matches)) = matches.subcommand() {
+        // cycle and info have no tick rate
+        if cli.fps.is_some()
+            && sub_matches.try_contains_id("tick_rate").is_ok()
+            && sub_matches.value_source("tick_rate") == Some(ValueSource::CommandLine)
+        {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "the argument '--fps <N>' cannot be used with '--tick-rate <MILLISECONDS>'",
+                )
+                .exit();
+        }
+    }
+    if let Commands::Info { format } = cli.command {
+        print_info(format);
+        return Ok(());
+    }
+    if let Some(path) = &cli.output_ppm {
+        return render_ppm(&cli.command, path, cli.at_tick);
+    }
+    load_key_bindings(cli.config.as_deref())?;
+    // every toy of a cycle would start a new viewport further down
+    if cli.inline.is_some() && matches!(cli.command, Commands::Cycle { .. }) {
+        return Err(eyre!("--inline doesn't work with cycle"));
+    }
+    let beat_fifo = match &cli.beat_fifo {
+        Some(path) => match cli.command {
+            Commands::Tunnel { .. } | Commands::Bubble { .. } | Commands::Cube { .. } => {
+                Some(BeatFifo::open(path)?)
+            }
+            _ => {
+                return Err(eyre!(
+                    "--beat-fifo is only supported by tunnel, bubble and cube"
+                ))
+            }
+        },
+        None => None,
+    };
+    let audio = if cli.audio_reactive {
+        match (&cli.command, cli.audio_param) {
+            (Commands::Tunnel { .. }, AudioParam::Amplitude) => {
+                return Err(eyre!("tunnel has no amplitude for --audio-param"))
+            }
+            (Commands::Cube { .. }, AudioParam::Colors) => {
+                return Err(eyre!("cube has no color count for --audio-param"))
+            }
+            (Commands::Tunnel { .. } | Commands::Bubble { .. } | Commands::Cube { .. }, _) => {}
+            _ => {
+                return Err(eyre!(
+                    "--audio-reactive is only supported by tunnel, bubble and cube"
+                ))
+            }
+        }
+        Some(AudioLevel::open(cli.audio_param)?)
+    } else {
+        None
+    };
+    if let Some(dir) = &cli.dump_frames {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut terminal = match cli.inline {
+        // needs the cursor position, which not every terminal reports
+        Some(height) => ratatui::try_init_with_options(TerminalOptions {
+            viewport: Viewport::Inline(height),
+        })
+        .inspect_err(|_| {
+            let _ = crossterm::terminal::disable_raw_mode();
+        })
+        .wrap_err("could not set up the --inline viewport")?,
+        None => ratatui::init(),
+    };
+    // the toys take the terminal, so remember where to put the cursor afterwards
+    let inline_bottom = cli.inline.map(|_| terminal.get_frame().area().bottom());
+    let mut options = cli.options;
+    options.detect_background();
+    options.frame_dumper = cli
+        .dump_frames
+        .clone()
+        .map(|dir| FrameDumper::new(dir, cli.max_frames));
+    let global_args = options.to_cli_args();
+    if let Commands::Cycle { interval, seed } = cli.command {
+        let result = run_cycle(terminal, interval, seed, options, cli.fps);
+        ratatui::restore();
+        return result;
+    }
+    let app_result = run_toy(
+        &cli.command,
+        terminal,
+        options.clone(),
+        cli.fps,
+        beat_fifo,
+        audio,
+    );
+    restore_terminal(inline_bottom);
+    let (args, summary) = app_result?;
+    if let Some(summary) = summary {
+        println!("{summary}");
+    }
+    if let Some(dumper) = options.frame_dumper.as_ref().filter(|_| cli.ping_pong) {
+        dumper.ping_pong()?;
+    }
+    if cli.verbose {
+        print_exit_summary(&format!("terminal-toys {global_args}{args}"));
+    } else if cli.print_config_on_exit {
+        println!("terminal-toys {global_args}{args}");
+    }
+    Ok(())
 }
 
-#[derive(Subcommand, Debug)]
-enum Commands {
-    /// Bouncy balls!
-    Balls {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
-        marker: Marker,
-
-        /// Number of balls to spawn
-        #[arg(short = 'n', long, value_name = "BALLS", default_value_t = 50)]
-        max_balls: u16,
-    },
-    /// 3d pipe screensaver
-    Pipes3d {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
-        marker: Marker,
-
-        /// Number of segments to generate before reset
-        #[arg(short = 'n', long, value_name = "SEGMENTS", default_value_t = 2000)]
-        max_segments: u32,
-
-        #[arg(short, long, value_name = "MILLISECONDS", default_value_t = 8)]
-        tick_rate: u64,
-
-        /// RNG seed
-        #[arg(short, long, value_name = "SEED", default_value_t = 99)]
-        seed: u64,
-
-        /// Use orthographic projection
-        #[arg(short, long, default_value_t = false)]
-        orthographic: bool,
-
-        /// Camera speed
-        #[arg(short = 'x', long, value_name = "SPEED", default_value_t = 4.0)]
-        camera_speed: f64,
-
-        /// Instead of resetting, delete earlier segments
-        #[arg(short, long, default_value_t = false)]
-        rotate: bool,
-    },
-    /// Lines that split after a while
-    Splits {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
-        marker: Marker,
-
-        /// Lines have a random rotation
-        #[arg(short, long, default_value_t = false)]
-        rotate: bool,
-
-        #[arg(short = 'n', long, value_name = "WALKERS", default_value_t = 80)]
-        max_walkers: u16,
-
-        /// RNG seed
-        #[arg(short, long, value_name = "SEED", default_value_t = 99)]
-        seed: u128,
-    },
-    /// Game of life
-    Life {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
-        marker: Marker,
-
-        /// Width of board (default: terminal width)
-        #[arg(short, long, value_name = "WIDTH")]
-        width: Option<usize>,
-
-        /// Ratio of live cells to start with
-        #[arg(short, value_name = "RATIO", default_value_t = 0.5)]
-        n: f32,
-
-        /// RNG seed
-        #[arg(short, long, value_name = "SEED", default_value_t = 3)]
-        seed: u128,
-    },
-    /// Falling sand
-    Sand {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
-        marker: Marker,
-
-        /// RNG seed
-        #[arg(short, long, value_name = "SEED", default_value_t = 0)]
-        seed: u128,
-
-        /// Speed multiplier
-        #[arg(short = 'x', long, value_name = "MULT", default_value_t = 1)]
-        speed: usize,
-
-        /// Number of obstacles
-        #[arg(short, long, value_name = "N", default_value_t = 40)]
-        obstacles: usize,
-
-        /// Length of obstacles
-        #[arg(short = 'l', long, value_name = "N", default_value_t = 5)]
-        obstacle_len: usize,
-
-        /// Average number of particles to spawn before changing spawn point
-        #[arg(short, long, value_name = "N", default_value_t = 100)]
-        particles: u64,
-
-        /// Flip after N ticks
-        #[arg(short, long, value_name = "N")]
-        flip_after: Option<u32>,
-
-        /// reset after sand emptied N times
-        #[arg(short, long, value_name = "N", default_value_t = 3)]
-        reset: usize,
-    },
-    /// Rotating tunnel
-    Tunnel {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
-        marker: Marker,
-
-        /// Number of colors
-        #[arg(short, long, value_name = "N", default_value_t = 16)]
-        n_colors: u8,
-
-        /// Rotation speed
-        #[arg(short = 'x', long, value_name = "SPEED", default_value_t = 1.0)]
-        speed: f64,
-
-        /// Amount of depth (0, 1, 2)
-        #[arg(short, long, default_value_t = 1)]
-        depth: u8,
-
-        /// Twisting tunnel
-        #[arg(short, long, default_value_t = false)]
-        twist: bool,
-    },
-    /// Langton's Ant
-    Ant {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
-        marker: Marker,
-
-        /// Width of board (default: terminal width)
-        #[arg(short, long, value_name = "WIDTH")]
-        width: Option<usize>,
-
-        /// Speed multiplier
-        #[arg(short = 'x', long, value_name = "MULT", default_value_t = 1)]
-        speed: usize,
-
-        /// Number of colors
-        #[arg(short, long, value_name = "N", default_value_t = 16, value_parser = clap::value_parser!(u8).range(2..))]
-        n_colors: u8,
-
-        /// Step distance = color
-        #[arg(short, long, default_value_t = false)]
-        dist_by_color: bool,
-
-        /// Fill in path
-        #[arg(short, long, default_value_t = false)]
-        filled: bool,
-
-        /// Pattern of starting active blocks [default: random]
-        #[arg(short, long, value_name = "INDEX", value_parser = clap::value_parser!(u8).range(0..6))]
-        pattern: Option<u8>,
-
-        /// Pattern length [default: random]
-        #[arg(short = 'l', long, value_name = "N")]
-        pattern_len: Option<usize>,
-
-        /// RNG seed
-        #[arg(short, long, value_name = "SEED", default_value_t = 99)]
-        seed: u128,
-    },
-    /// Bubble universe by A-na5 / ｱ_ﾅ
-    Bubble {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::HalfBlock)]
-        marker: Marker,
-
-        /// Parameter a
-        #[arg(short, value_name = "N", default_value_t = 30)]
-        a: u32,
-
-        /// Parameter b
-        #[arg(short, value_name = "N", default_value_t = 30)]
-        b: u32,
-
-        /// Number of colors
-        #[arg(short, long, value_name = "N", default_value_t = 16, value_parser = clap::value_parser!(u8).range(2..))]
-        n_colors: u8,
-    },
-    /// Rotating sine wave cube
-    Cube {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
-        marker: Marker,
-
-        #[arg(short, long, value_name = "MILLISECONDS", default_value_t = 8)]
-        tick_rate: u64,
-
-        /// Use orthographic projection
-        #[arg(short, long, default_value_t = false)]
-        orthographic: bool,
-
-        #[arg(short, long, value_name = "SPEED", default_value_t = 1.0)]
-        x_rotation_speed: f64,
-
-        #[arg(short, long, value_name = "SPEED", default_value_t = 0.5)]
-        y_rotation_speed: f64,
-
-        #[arg(short, long, value_name = "SPEED", default_value_t = 0.25)]
-        z_rotation_speed: f64,
-
-        /// Amplitude of sine waves
-        #[arg(short, long, value_name = "AMPLITUDE", default_value_t = 1.2)]
-        amplitude: f64,
-
-        /// Frequency of sine waves
-        #[arg(short, long, value_name = "FREQUENCY", default_value_t = 1.0)]
-        frequency: f64,
-
-        /// Speed of sine wave phase shift
-        #[arg(short, long, value_name = "SPEED", default_value_t = 1.0)]
-        speed: f64,
-
-        /// Color change speed
-        #[arg(short, long, value_name = "SPEED")]
-        color_speed: Option<f64>,
-    },
-    /// Sphere made out of shifting rings
-    Rings {
-        /// Marker type (Braille, Dot, Bar, Block, HalfBlock, Quadrant, Sextant, Octant)
-        #[arg(short, long, value_name = "TYPE", default_value_t = Marker::Braille)]
-        marker: Marker,
-
-        #[arg(short, long, value_name = "MILLISECONDS", default_value_t = 8)]
-        tick_rate: u64,
-
-        /// Use orthographic projection
-        #[arg(short, long, default_value_t = false)]
-        orthographic: bool,
-
-        #[arg(short, long, value_name = "SPEED", default_value_t = 0.5)]
-        x_rotation_speed: f64,
-
-        #[arg(short, long, value_name = "SPEED", default_value_t = 0.25)]
-        y_rotation_speed: f64,
-
-        #[arg(short, long, value_name = "SPEED", default_value_t = 0.125)]
-        z_rotation_speed: f64,
-
-        /// Amplitude of sine waves
-        #[arg(short, long, value_name = "AMPLITUDE", default_value_t = 5.0)]
-        amplitude: f64,
-
-        /// Frequency of sine waves
-        #[arg(short, long, value_name = "FREQUENCY", default_value_t = 10.0)]
-        frequency: f64,
-
-        /// Speed of sine wave phase shift
-        #[arg(short, long, value_name = "SPEED", default_value_t = 0.1)]
-        speed: f64,
+/// --verbose: how to get the same scene again, and where terminal-toys comes from
+fn print_exit_summary(command_line: &str) {
+    let url = env!("CARGO_PKG_REPOSITORY");
+    println!("reproduce with: {command_line}");
+    // OSC 8 link, terminals without support show the text and drop the escapes
+    let link = if io::stdout().is_terminal() {
+        format!("\x1b]8;;{url}\x1b\\{url}\x1b]8;;\x1b\\")
+    } else {
+        url.to_string()
+    };
+    println!("terminal-toys {}: {link}", env!("CARGO_PKG_VERSION"));
+}
 
-        /// Zoom
-        #[arg(
-            long,
-            value_name = "ZOOM",
-            default_value_t = 0.0,
-            allow_negative_numbers = true
-        )]
-        zoom: f64,
-    },
+/// undo ratatui::init, with --inline `bottom` is the row below the viewport and the cursor
+/// goes there instead of leaving an alternate screen that was never entered
+fn restore_terminal(inline_bottom: Option<u16>) {
+    let Some(bottom) = inline_bottom else {
+        ratatui::restore();
+        return;
+    };
+    let _ = crossterm::terminal::disable_raw_mode();
+    // the viewport may end on the last row, a newline from the row above scrolls if needed
+    let _ = execute!(io::stdout(), MoveTo(0, bottom.saturating_sub(1)));
+    println!();
 }
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
-    let cli = Cli::parse();
-    let terminal = ratatui::init();
-    let size = terminal.size().unwrap();
-    let app_result = match &cli.command {
-        Commands::Balls { marker, max_balls } => {
-            balls::App::new(size.width, size.height, *marker, *max_balls).run(terminal)
-        }
+/// run a single toy until it quits, returns its settings as command line arguments and
+/// a line to print afterwards for toys that have something to report
+fn run_toy(
+    command: &Commands,
+    mut terminal: DefaultTerminal,
+    options: RunOptions,
+    fps: Option<u64>,
+    beat_fifo: Option<BeatFifo>,
+    audio: Option<AudioLevel>,
+) -> Result<(String, Option<String>)> {
+    // the viewport, smaller than the terminal with --inline
+    let size = terminal.get_frame().area();
+    let effective_tick_rate = |tick_rate: &u64| fps.map_or(*tick_rate, |fps| (1000 / fps).max(1));
+    match command {
+        Commands::Balls {
+            marker,
+            tick_rate,
+            max_balls,
+            ceiling,
+            wrap,
+            trail,
+            seed,
+            jitter,
+        } => balls::App::new(
+            size.width,
+            size.height,
+            *marker,
+            *max_balls,
+            *ceiling,
+            *wrap,
+            *trail,
+            *seed,
+            *jitter,
+        )
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
+        Commands::Boids {
+            marker,
+            tick_rate,
+            count,
+            seed,
+            separation,
+            alignment,
+            cohesion,
+        } => boids::App::new(
+            size.width,
+            size.height,
+            *marker,
+            *count,
+            *seed,
+            *separation,
+            *alignment,
+            *cohesion,
+        )
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Pipes3d {
             marker,
             max_segments,
@@ -310,6 +221,15 @@ fn main() -> Result<()> {
             orthographic,
             camera_speed,
             rotate,
+            fov,
+            fov_step,
+            color_mode,
+            anaglyph,
+            eye_separation,
+            free_cam,
+            thickness,
+            z_sort,
+            skip_unchanged,
         } => pipes3d::App::new(
             size.width,
             size.height,
@@ -317,56 +237,207 @@ fn main() -> Result<()> {
             *max_segments,
             *orthographic,
             *rotate,
+            *fov,
+            *fov_step,
+            *color_mode,
+            *anaglyph,
+            *eye_separation,
+            *free_cam,
+            *thickness,
+            *z_sort,
+            *skip_unchanged,
+        )
+        .run(
+            terminal,
+            effective_tick_rate(tick_rate),
+            *seed,
+            *camera_speed,
+            options,
+        )
+        .map(|app| (app.to_cli_args(), None)),
+        Commands::Pipes2d {
+            marker,
+            max_segments,
+            pipes,
+            tick_rate,
+            seed,
+        } => pipes2d::App::new(
+            size.width,
+            size.height,
+            *marker,
+            *max_segments,
+            *pipes,
+            *seed,
         )
-        .run(terminal, *tick_rate, *seed, *camera_speed),
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Splits {
             marker,
+            tick_rate,
             rotate,
             max_walkers,
+            n_colors,
+            continuous,
+            bounce,
             seed,
+            thickness,
+            max_history,
         } => splits::App::new(
             size.width,
             size.height,
             *marker,
             *rotate,
             *max_walkers,
+            *n_colors,
+            *continuous,
+            *bounce,
             *seed,
+            *thickness,
+            *max_history,
         )
-        .run(terminal),
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Life {
             marker,
+            tick_rate,
             seed,
             n,
+            n_colors,
+            width,
+            height,
+            edges,
+            ascii,
+            no_reset,
+            show_grid,
+            color_rule,
+            history,
+            freeze_on_stable,
+        } => life::App::new(
+            size.width,
+            size.height,
+            *marker,
+            *seed,
+            *n,
+            *n_colors,
+            *width,
+            *height,
+            *edges,
+            *ascii,
+            *no_reset,
+            *show_grid,
+            *color_rule,
+            *history,
+            *freeze_on_stable,
+        )
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), app.summary())),
+        Commands::Maze {
+            marker,
+            tick_rate,
             width,
-        } => life::App::new(size.width, size.height, *marker, *seed, *n, *width).run(terminal),
+            speed,
+            algorithm,
+            solver,
+            seed,
+        } => maze::App::new(
+            size.width,
+            size.height,
+            *marker,
+            *width,
+            *speed,
+            *algorithm,
+            *solver,
+            *seed,
+        )
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Sand {
             marker,
+            tick_rate,
             seed,
+            obstacle_seed,
             speed,
             obstacles,
             obstacle_len,
+            obstacle_color,
+            terrain,
+            terrain_density,
+            burying,
             particles,
+            spawn_rate,
             flip_after,
             reset,
+            emitters,
+            emitter_mode,
+            ascii,
+            wind,
+            mix,
+            no_reset,
+            stats,
         } => sand::App::new(
             size.width,
             size.height,
             *marker,
             *seed,
+            *obstacle_seed,
             *speed,
             *obstacles,
             *obstacle_len,
+            *obstacle_color,
+            terrain.then_some(*terrain_density),
+            *burying,
             *particles,
+            *spawn_rate,
             *flip_after,
             *reset,
+            *emitters,
+            *emitter_mode,
+            *ascii,
+            *wind,
+            *mix,
+            *no_reset,
+            *stats,
+        )
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
+        Commands::Stars {
+            marker,
+            tick_rate,
+            count,
+            speed,
+            seed,
+        } => stars::App::new(size.width, size.height, *marker, *count, *speed, *seed)
+            .run(terminal, effective_tick_rate(tick_rate), options)
+            .map(|app| (app.to_cli_args(), None)),
+        Commands::Rain {
+            marker,
+            tick_rate,
+            intensity,
+            wind,
+            obstacles,
+            seed,
+        } => rain::App::new(
+            size.width,
+            size.height,
+            *marker,
+            *intensity,
+            *wind,
+            *obstacles,
+            *seed,
         )
-        .run(terminal),
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Tunnel {
             marker,
+            tick_rate,
             n_colors,
             speed,
             depth,
             twist,
+            mirror,
+            ascii,
+            persistence,
+            smooth,
         } => tunnel::App::new(
             size.width,
             size.height,
@@ -375,38 +446,87 @@ fn main() -> Result<()> {
             *speed,
             *depth,
             *twist,
+            *mirror,
+            *ascii,
+            *persistence,
+            *smooth,
         )
-        .run(terminal),
+        .run(
+            terminal,
+            effective_tick_rate(tick_rate),
+            beat_fifo,
+            audio,
+            options,
+        )
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Ant {
             marker,
+            tick_rate,
             speed,
             width,
+            height,
             n_colors,
             dist_by_color,
             filled,
+            edges,
             pattern,
             pattern_len,
             seed,
+            no_reset,
+            scroll,
+            reset_threshold,
+            diagonal,
         } => ant::App::new(
             size.width,
             size.height,
             *marker,
             *speed,
             *width,
+            *height,
             *n_colors,
             *dist_by_color,
             *filled,
+            *edges,
             *pattern,
             *pattern_len,
             *seed,
+            *no_reset,
+            *scroll,
+            *reset_threshold,
+            *diagonal,
         )
-        .run(terminal),
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Bubble {
             marker,
+            tick_rate,
             n_colors,
             a,
             b,
-        } => bubble::App::new(size.width, size.height, *marker, *n_colors, *a, *b).run(terminal),
+            ratio,
+            mirror,
+            ascii,
+            persistence,
+        } => bubble::App::new(
+            size.width,
+            size.height,
+            *marker,
+            *n_colors,
+            *a,
+            *b,
+            *ratio,
+            *mirror,
+            *ascii,
+            *persistence,
+        )
+        .run(
+            terminal,
+            effective_tick_rate(tick_rate),
+            beat_fifo,
+            audio,
+            options,
+        )
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Cube {
             marker,
             tick_rate,
@@ -417,7 +537,18 @@ fn main() -> Result<()> {
             amplitude,
             frequency,
             speed,
+            no_wave,
             color_speed,
+            fov,
+            fov_step,
+            anaglyph,
+            eye_separation,
+            thickness,
+            z_sort,
+            solid,
+            resolution,
+            wireframe,
+            skip_unchanged,
         } => cube::App::new(
             size.width,
             size.height,
@@ -426,12 +557,29 @@ fn main() -> Result<()> {
             *x_rotation_speed,
             *y_rotation_speed,
             *z_rotation_speed,
-            *amplitude,
+            if *no_wave { 0.0 } else { *amplitude },
             *frequency,
             *speed,
             *color_speed,
+            *fov,
+            *fov_step,
+            *anaglyph,
+            *eye_separation,
+            *thickness,
+            *z_sort,
+            *solid,
+            *resolution,
+            *wireframe,
+            *skip_unchanged,
+        )
+        .run(
+            terminal,
+            effective_tick_rate(tick_rate),
+            beat_fifo,
+            audio,
+            options,
         )
-        .run(terminal, *tick_rate),
+        .map(|app| (app.to_cli_args(), None)),
         Commands::Rings {
             marker,
             tick_rate,
@@ -456,8 +604,181 @@ fn main() -> Result<()> {
             *speed,
             *zoom,
         )
-        .run(terminal, *tick_rate),
+        .run(terminal, effective_tick_rate(tick_rate), options)
+        .map(|app| (app.to_cli_args(), None)),
+        Commands::Cycle { .. } | Commands::Info { .. } => {
+            unreachable!("cycle and info don't run a single toy")
+        }
+    }
+}
+
+/// show every toy with its defaults for `interval` seconds each until a quit key,
+/// in order or shuffled by `seed`
+fn run_cycle(
+    mut terminal: DefaultTerminal,
+    interval: u64,
+    seed: Option<u64>,
+    mut options: RunOptions,
+    fps: Option<u64>,
+) -> Result<()> {
+    let mut toys: Vec<String> = Cli::command()
+        .get_subcommands()
+        .map(|command| command.get_name().to_string())
+        .filter(|name| name != "cycle" && name != "info")
+        .collect();
+    let mut rng = seed.map(|seed| oorandom::Rand64::new(seed as u128));
+    loop {
+        if let Some(rng) = &mut rng {
+            // fisher-yates
+            for i in (1..toys.len()).rev() {
+                let j = rng.rand_range(0..i as u64 + 1) as usize;
+                toys.swap(i, j);
+            }
+        }
+        for toy in &toys {
+            let command = Cli::try_parse_from(["terminal-toys", toy])?.command;
+            options.deadline = Some(Instant::now() + Duration::from_secs(interval));
+            terminal.clear()?;
+            run_toy(&command, terminal, options.clone(), fps, None, None)?;
+            if !options.turn_over() && !RunOptions::take_turn_skipped() {
+                return Ok(());
+            }
+            // every toy takes the terminal, start the next one on a fresh one
+            terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        }
+    }
+}
+
+/// render one frame to an image without touching the terminal
+fn render_ppm(command: &Commands, path: &Path, tick: u64) -> Result<()> {
+    let (width, height) = crossterm::terminal::size().unwrap_or((80, 24));
+    let ppm = match command {
+        Commands::Tunnel {
+            marker,
+            n_colors,
+            speed,
+            depth,
+            twist,
+            mirror,
+            ..
+        } => {
+            let mut app = tunnel::App::new(
+                width, height, *marker, *n_colors, *speed, *depth, *twist, *mirror, false, 0.0,
+                false,
+            );
+            app.set_tick(tick);
+            app.render_to_ppm()
+        }
+        Commands::Bubble {
+            marker,
+            n_colors,
+            a,
+            b,
+            ratio,
+            mirror,
+            ..
+        } => {
+            let mut app = bubble::App::new(
+                width, height, *marker, *n_colors, *a, *b, *ratio, *mirror, false, 0.0,
+            );
+            app.set_tick(tick);
+            app.render_to_ppm()
+        }
+        _ => return Err(eyre!("--output-ppm is only supported by tunnel and bubble")),
     };
-    ratatui::restore();
-    app_result
+    std::fs::write(path, ppm)?;
+    Ok(())
+}
+
+/// version requirement of a dependency as written in Cargo.toml, not the version cargo
+/// picked for the build
+fn dependency_requirement(name: &str) -> String {
+    let manifest: toml::Table = include_str!("../Cargo.toml").parse().unwrap_or_default();
+    let dependency = manifest
+        .get("dependencies")
+        .and_then(|dependencies| dependencies.get(name));
+    // `name = "1.0"` or `name = { version = "1.0", ... }`
+    dependency
+        .and_then(|dependency| dependency.get("version").or(Some(dependency)))
+        .and_then(|version| version.as_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+/// `s` as a JSON string literal
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\r' => json += "\\r",
+            '\t' => json += "\\t",
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// version, terminal and per toy details, works without a terminal too
+fn print_info(format: InfoFormat) {
+    let size = match crossterm::terminal::size() {
+        Ok((width, height)) => format!("{width}x{height}"),
+        Err(_) => "unknown".to_string(),
+    };
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    let truecolor = supports_truecolor();
+    let markers: Vec<(String, String)> = Cli::command()
+        .get_subcommands()
+        .filter_map(|command| {
+            let marker = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == "marker")?;
+            let default = marker
+                .get_default_values()
+                .first()
+                .map_or("none".to_string(), |value| {
+                    value.to_string_lossy().into_owned()
+                });
+            Some((command.get_name().to_string(), default))
+        })
+        .collect();
+
+    match format {
+        InfoFormat::Text => {
+            println!("terminal-toys {}", env!("CARGO_PKG_VERSION"));
+            println!("terminal size: {size}");
+            println!("truecolor: {truecolor} (COLORTERM={colorterm})");
+            println!("ratatui requirement: {}", dependency_requirement("ratatui"));
+            println!("glam requirement: {}", dependency_requirement("glam"));
+            println!("default markers:");
+            for (toy, marker) in &markers {
+                println!("  {toy}: {marker}");
+            }
+        }
+        InfoFormat::Json => {
+            let markers: Vec<String> = markers
+                .iter()
+                .map(|(toy, marker)| format!("{}: {}", json_string(toy), json_string(marker)))
+                .collect();
+            println!("{{");
+            println!("  \"version\": {},", json_string(env!("CARGO_PKG_VERSION")));
+            println!("  \"terminal_size\": {},", json_string(&size));
+            println!("  \"truecolor\": {truecolor},");
+            println!("  \"colorterm\": {},", json_string(&colorterm));
+            println!(
+                "  \"ratatui_requirement\": {},",
+                json_string(&dependency_requirement("ratatui"))
+            );
+            println!(
+                "  \"glam_requirement\": {},",
+                json_string(&dependency_requirement("glam"))
+            );
+            println!("  \"default_markers\": {{ {} }}", markers.join(", "));
+            println!("}}");
+        }
+    }
 }
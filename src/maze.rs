@@ -0,0 +1,483 @@
+use crate::gamepad;
+use crate::input::{self, Action};
+use crate::utils::{
+    calc_board_size_stretched, map_range, next_seed, value_enum_name, Backoff, RunOptions,
+};
+use clap::ValueEnum;
+use color_eyre::Result;
+use glam::DVec2;
+use oorandom::Rand64;
+use ratatui::{
+    crossterm::event::{self, Event},
+    style::Color,
+    symbols::Marker,
+    widgets::{
+        canvas::{Canvas, Points, Rectangle},
+        Paragraph, Widget,
+    },
+    DefaultTerminal, Frame,
+};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Algorithm {
+    /// Recursive backtracker, long winding corridors
+    #[default]
+    Backtracker,
+    /// Randomized Prim's, lots of short dead ends
+    Prim,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Solver {
+    /// Breadth-first search
+    #[default]
+    Bfs,
+    /// Depth-first search
+    Dfs,
+    /// A* with manhattan distance
+    Astar,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Wall,
+    Open,
+    Carving,
+    Frontier,
+    Visited,
+    Path,
+}
+
+impl Cell {
+    fn color(self) -> Option<Color> {
+        match self {
+            Cell::Wall => None,
+            Cell::Open => Some(Color::Indexed(8)),
+            Cell::Carving => Some(Color::Indexed(9)),
+            Cell::Frontier => Some(Color::Indexed(11)),
+            Cell::Visited => Some(Color::Indexed(4)),
+            Cell::Path => Some(Color::Indexed(10)),
+        }
+    }
+}
+
+enum Phase {
+    Carving,
+    Solving,
+    Done,
+}
+
+type Pos = (usize, usize);
+
+pub struct App {
+    grid: Vec<Vec<Cell>>,
+    exit: bool,
+    playground: DVec2,
+    debug_text: String,
+    quiet: bool,
+    marker: Marker,
+    pixel: bool,
+    rng: Rand64,
+    speed: usize,
+    algorithm: Algorithm,
+    solver: Solver,
+    phase: Phase,
+    ticks_since_done: u64,
+    // carving: backtracker stack or prim frontier
+    carve_list: Vec<Pos>,
+    // solving
+    queue: VecDeque<Pos>,
+    heap: BinaryHeap<Reverse<(usize, usize, Pos)>>,
+    came_from: Vec<Vec<Option<Pos>>>,
+    distance: Vec<Vec<usize>>,
+    loop_seed: bool,
+    tick_rate: u64,
+    seed: u128,
+}
+
+impl App {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        terminal_width: u16,
+        terminal_height: u16,
+        marker: Marker,
+        board_width: Option<usize>,
+        speed: usize,
+        algorithm: Algorithm,
+        solver: Solver,
+        seed: u128,
+    ) -> Self {
+        let scale_factor = terminal_height as f32 / terminal_width as f32;
+        let font_scale_factor = 2.0;
+        let width = 200.0;
+        let height = width * scale_factor * font_scale_factor;
+        let wh_factor = height / width;
+        let pixel = board_width.is_none();
+
+        let (board_width, board_height) = match board_width {
+            Some(width) => (width, (width as f32 * wh_factor) as usize),
+            None => calc_board_size_stretched(marker, terminal_width, terminal_height),
+        };
+        // walls sit on even coordinates, rooms on odd ones,
+        // so both sides need to be odd and at least 3
+        let board_width = (board_width.max(3) - 1) | 1;
+        let board_height = (board_height.max(3) - 1) | 1;
+
+        Self {
+            grid: vec![vec![Cell::Wall; board_width]; board_height],
+            exit: false,
+            playground: DVec2::new(width as f64, height as f64),
+            debug_text: String::new(),
+            marker,
+            pixel,
+            rng: oorandom::Rand64::new(seed),
+            speed,
+            algorithm,
+            solver,
+            phase: Phase::Carving,
+            ticks_since_done: 0,
+            carve_list: Vec::new(),
+            queue: VecDeque::new(),
+            heap: BinaryHeap::new(),
+            came_from: vec![vec![None; board_width]; board_height],
+            distance: vec![vec![usize::MAX; board_width]; board_height],
+            loop_seed: false,
+            quiet: false,
+            tick_rate: 0,
+            seed,
+        }
+    }
+
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        self.loop_seed = options.loop_seed;
+        let tick_rate = Duration::from_millis(tick_rate);
+        let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
+        self.reset();
+
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
+            if event::poll(timeout)? {
+                backoff.reset();
+                match event::read()? {
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
+                    Event::Resize(_columns, _rows) => {
+                        // self.debug_text = format!("{} {}", columns, rows);
+                    }
+                    _ => (),
+                }
+            }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
+
+            if last_tick.elapsed() >= tick_rate {
+                if let Phase::Done = self.phase {
+                    self.ticks_since_done += 1;
+                    if self.ticks_since_done > 200 {
+                        self.auto_reset();
+                    }
+                } else {
+                    for _ in 0..self.speed {
+                        self.on_tick();
+                    }
+                }
+                last_tick = Instant::now();
+            }
+        }
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "maze --marker {} --tick-rate {} --speed {} --algorithm {} --solver {} --seed {}",
+            self.marker,
+            self.tick_rate,
+            self.speed,
+            value_enum_name(self.algorithm),
+            value_enum_name(self.solver),
+            self.seed
+        );
+        if !self.pixel {
+            args += &format!(" --width {}", self.grid[0].len());
+        }
+        args
+    }
+
+    /// reset on its own, starting from a new seed with --loop-seed
+    fn auto_reset(&mut self) {
+        if self.loop_seed {
+            self.seed = next_seed(&mut self.rng);
+            self.rng = oorandom::Rand64::new(self.seed);
+        }
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        for line in self.grid.iter_mut() {
+            for cell in line.iter_mut() {
+                *cell = Cell::Wall;
+            }
+        }
+        self.phase = Phase::Carving;
+        self.ticks_since_done = 0;
+        self.carve_list.clear();
+
+        let start = (1, 1);
+        match self.algorithm {
+            Algorithm::Backtracker => {
+                self.grid[start.1][start.0] = Cell::Carving;
+                self.carve_list.push(start);
+            }
+            Algorithm::Prim => {
+                self.grid[start.1][start.0] = Cell::Open;
+                self.add_frontier(start);
+            }
+        }
+    }
+
+    fn start_solving(&mut self) {
+        self.phase = Phase::Solving;
+        self.queue.clear();
+        self.heap.clear();
+        for line in self.came_from.iter_mut() {
+            line.fill(None);
+        }
+        for line in self.distance.iter_mut() {
+            line.fill(usize::MAX);
+        }
+        let start = (1, 1);
+        self.distance[start.1][start.0] = 0;
+        self.grid[start.1][start.0] = Cell::Frontier;
+        match self.solver {
+            Solver::Bfs | Solver::Dfs => self.queue.push_back(start),
+            Solver::Astar => self.heap.push(Reverse((self.heuristic(start), 0, start))),
+        }
+    }
+
+    fn goal(&self) -> Pos {
+        (self.grid[0].len() - 2, self.grid.len() - 2)
+    }
+
+    fn heuristic(&self, (x, y): Pos) -> usize {
+        let (goal_x, goal_y) = self.goal();
+        goal_x.abs_diff(x) + goal_y.abs_diff(y)
+    }
+
+    /// rooms two steps away in each direction, with the wall in between
+    fn room_neighbors(&self, (x, y): Pos) -> Vec<(Pos, Pos)> {
+        let width = self.grid[0].len();
+        let height = self.grid.len();
+        let mut neighbors = Vec::with_capacity(4);
+        if x >= 3 {
+            neighbors.push(((x - 2, y), (x - 1, y)));
+        }
+        if x + 2 < width {
+            neighbors.push(((x + 2, y), (x + 1, y)));
+        }
+        if y >= 3 {
+            neighbors.push(((x, y - 2), (x, y - 1)));
+        }
+        if y + 2 < height {
+            neighbors.push(((x, y + 2), (x, y + 1)));
+        }
+        neighbors
+    }
+
+    fn add_frontier(&mut self, pos: Pos) {
+        for ((x, y), _) in self.room_neighbors(pos) {
+            if self.grid[y][x] == Cell::Wall {
+                self.grid[y][x] = Cell::Frontier;
+                self.carve_list.push((x, y));
+            }
+        }
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.exit = true,
+            Action::Reset => self.reset(),
+            // there are only two algorithms, so both directions switch
+            Action::NextSeed | Action::PrevSeed => {
+                self.algorithm = match self.algorithm {
+                    Algorithm::Backtracker => Algorithm::Prim,
+                    Algorithm::Prim => Algorithm::Backtracker,
+                };
+                self.reset();
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_key_press(&mut self, key: event::KeyEvent) {
+        if let Some(action) = input::map_key(key) {
+            self.apply(action);
+        }
+    }
+
+    fn on_tick(&mut self) {
+        match self.phase {
+            Phase::Carving => self.carve_step(),
+            Phase::Solving => self.solve_step(),
+            Phase::Done => (),
+        }
+    }
+
+    fn carve_step(&mut self) {
+        match self.algorithm {
+            Algorithm::Backtracker => {
+                let Some(&current) = self.carve_list.last() else {
+                    self.start_solving();
+                    return;
+                };
+                let unvisited: Vec<(Pos, Pos)> = self
+                    .room_neighbors(current)
+                    .into_iter()
+                    .filter(|((x, y), _)| self.grid[*y][*x] == Cell::Wall)
+                    .collect();
+                if unvisited.is_empty() {
+                    self.grid[current.1][current.0] = Cell::Open;
+                    self.carve_list.pop();
+                    return;
+                }
+                let i = self.rng.rand_range(0..unvisited.len() as u64) as usize;
+                let (next, wall) = unvisited[i];
+                self.grid[wall.1][wall.0] = Cell::Open;
+                self.grid[next.1][next.0] = Cell::Carving;
+                self.carve_list.push(next);
+            }
+            Algorithm::Prim => {
+                if self.carve_list.is_empty() {
+                    self.start_solving();
+                    return;
+                }
+                let i = self.rng.rand_range(0..self.carve_list.len() as u64) as usize;
+                let current = self.carve_list.swap_remove(i);
+                let carved: Vec<(Pos, Pos)> = self
+                    .room_neighbors(current)
+                    .into_iter()
+                    .filter(|((x, y), _)| self.grid[*y][*x] == Cell::Open)
+                    .collect();
+                let i = self.rng.rand_range(0..carved.len() as u64) as usize;
+                let (_, wall) = carved[i];
+                self.grid[wall.1][wall.0] = Cell::Open;
+                self.grid[current.1][current.0] = Cell::Open;
+                self.add_frontier(current);
+            }
+        }
+    }
+
+    fn solve_step(&mut self) {
+        let current = match self.solver {
+            Solver::Bfs => self.queue.pop_front(),
+            Solver::Dfs => self.queue.pop_back(),
+            Solver::Astar => self.heap.pop().map(|Reverse((_, _, pos))| pos),
+        };
+        let Some(current) = current else {
+            // unreachable goal, shouldn't happen in a perfect maze
+            self.phase = Phase::Done;
+            return;
+        };
+        if self.grid[current.1][current.0] == Cell::Visited {
+            return;
+        }
+        self.grid[current.1][current.0] = Cell::Visited;
+
+        if current == self.goal() {
+            let mut pos = Some(current);
+            while let Some((x, y)) = pos {
+                self.grid[y][x] = Cell::Path;
+                pos = self.came_from[y][x];
+            }
+            self.phase = Phase::Done;
+            return;
+        }
+
+        let (x, y) = current;
+        let distance = self.distance[y][x] + 1;
+        for next in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            let cell = self.grid[next.1][next.0];
+            if cell == Cell::Wall || cell == Cell::Visited {
+                continue;
+            }
+            if distance >= self.distance[next.1][next.0] {
+                continue;
+            }
+            self.distance[next.1][next.0] = distance;
+            self.came_from[next.1][next.0] = Some(current);
+            self.grid[next.1][next.0] = Cell::Frontier;
+            match self.solver {
+                Solver::Bfs | Solver::Dfs => self.queue.push_back(next),
+                Solver::Astar => {
+                    let cost = distance + self.heuristic(next);
+                    self.heap.push(Reverse((cost, distance, next)));
+                }
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self.canvas(), frame.area());
+        if !self.quiet && !self.debug_text.is_empty() {
+            let debug_text = Paragraph::new(self.debug_text.clone());
+            frame.render_widget(debug_text, frame.area());
+        }
+    }
+
+    fn canvas(&self) -> impl Widget + '_ {
+        Canvas::default()
+            .marker(self.marker)
+            .paint(|ctx| {
+                if self.grid.is_empty() {
+                    return;
+                }
+                let width = self.grid[0].len();
+                let height = self.grid.len();
+                let square_width = self.playground.x / width as f64;
+                let square_height = self.playground.y / height as f64;
+
+                for (y, line) in self.grid.iter().enumerate() {
+                    let y = map_range(y as f64, 0.0, height as f64, 0.0, self.playground.y);
+                    for (x, cell) in line.iter().enumerate() {
+                        let x = map_range(x as f64, 0.0, width as f64, 0.0, self.playground.x);
+                        let Some(color) = cell.color() else {
+                            continue;
+                        };
+                        if self.pixel {
+                            ctx.draw(&Points {
+                                coords: &[(x, y)],
+                                color,
+                            });
+                        } else {
+                            let square = Rectangle {
+                                x,
+                                y,
+                                width: square_width,
+                                height: square_height,
+                                color,
+                            };
+                            ctx.draw(&square);
+                        }
+                    }
+                }
+            })
+            .x_bounds([0.0, self.playground.x])
+            .y_bounds([0.0, self.playground.y])
+    }
+}
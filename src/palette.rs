@@ -0,0 +1,50 @@
+//! Color-blind friendly replacements for the 16 basic colors.
+//!
+//! The toys that hand out several colors (tunnel, bubble, sand, splits, life, ant) count
+//! through the basic colors, so swapping those at the draw layer is enough to recolor them.
+//! The replacements come from the 256 color palette like everything else.
+
+use crate::utils::color_index;
+use clap::ValueEnum;
+use ratatui::style::Color;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Palette {
+    /// The terminal's own colors
+    #[default]
+    Default,
+    /// No red/green pairs, for weak green cones
+    Deuteranopia,
+    /// No red/green pairs and no dark reds, for weak red cones
+    Protanopia,
+    /// No blue/yellow pairs, for weak blue cones
+    Tritanopia,
+}
+
+// replacements for basic colors 1..=15, black stays black.
+// built around the Okabe-Ito colors, then lighter and darker steps of the hues that stay apart
+const DEUTERANOPIA: [u8; 15] = [
+    214, 74, 36, 227, 25, 166, 175, 246, 17, 136, 153, 230, 61, 180, 255,
+];
+const PROTANOPIA: [u8; 15] = [
+    33, 220, 19, 229, 75, 136, 111, 94, 189, 178, 24, 222, 147, 58, 255,
+];
+const TRITANOPIA: [u8; 15] = [
+    160, 37, 210, 23, 218, 30, 88, 123, 199, 66, 224, 52, 87, 132, 255,
+];
+
+impl Palette {
+    /// `color` as drawn with this palette, only the basic colors change
+    pub fn apply(self, color: Color) -> Color {
+        let replacements = match self {
+            Palette::Default => return color,
+            Palette::Deuteranopia => &DEUTERANOPIA,
+            Palette::Protanopia => &PROTANOPIA,
+            Palette::Tritanopia => &TRITANOPIA,
+        };
+        match color_index(color) {
+            Some(index @ 1..16) => Color::Indexed(replacements[index as usize - 1]),
+            _ => color,
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Grid handling shared by the falling particle toys (sand, rain).
+//!
+//! Row 0 is the top of the screen, particles fall towards higher row indices.
+
+use crate::grid::CellGrid;
+use crate::utils::{calc_board_size_scaled, map_range};
+use oorandom::Rand64;
+use ratatui::{
+    style::Color,
+    symbols::Marker,
+    widgets::canvas::{Context, Points},
+};
+
+/// None = empty, Some(1) = obstacle, Some(n) = particle
+pub type CellState = Option<u8>;
+pub type Grid = CellGrid<CellState>;
+
+pub const OBSTACLE: u8 = 1;
+
+pub fn new_grid(marker: Marker, terminal_width: u16, terminal_height: u16) -> Grid {
+    let (board_width, board_height) =
+        calc_board_size_scaled(marker, terminal_width, terminal_height);
+    CellGrid::new(board_width, board_height, None)
+}
+
+/// most obstacles that still leave room for the sand, one per 10 cells
+pub fn max_obstacles(grid: &Grid) -> usize {
+    let (board_width, board_height) = grid.dimensions();
+    board_width * board_height / 10
+}
+
+/// scatter short straight or slanted obstacles over the middle 80% of the grid,
+/// at most max_obstacles() of them
+pub fn place_obstacles(grid: &mut Grid, rng: &mut Rand64, obstacles: usize, max_len: usize) {
+    let (board_width, board_height) = grid.dimensions();
+    if board_width == 0 || board_height == 0 {
+        return;
+    }
+    let obstacles = obstacles.min(max_obstacles(grid));
+    let board_width = board_width as u64;
+    let bounds_x = (0, board_width);
+    let bounds_y = (
+        (board_height as f64 * 0.1) as u64,
+        (board_height as f64 * 0.90) as u64,
+    );
+    for _ in 0..obstacles {
+        let r = rng.rand_range(50..100) as f32 * 0.01;
+        let mut obstacle_len = (board_width as f64 * 0.15) as i32;
+        obstacle_len =
+            (obstacle_len.min(max_len.min(board_width as usize) as i32) as f32 * r) as i32;
+
+        let x0 = rng.rand_range(bounds_x.0..bounds_x.1) as i32;
+        let y0 = rng.rand_range(bounds_y.0..bounds_y.1) as i32;
+        let sign = match rng.rand_range(0..2) {
+            0 => -1,
+            1 => 1,
+            _ => unreachable!(),
+        };
+        let y_mult = match rng.rand_range(0..3) {
+            0 => 1.0,
+            1 => 0.7,
+            2 => 0.0,
+            _ => unreachable!(),
+        };
+        for i in 0..obstacle_len {
+            let x = (x0 + sign * i) as usize;
+            let y = (y0 + (y_mult * i as f64) as i32) as usize;
+            if x >= board_width as usize || y >= board_height {
+                continue;
+            }
+            if let Some(OBSTACLE) = grid[(x, y)] {
+                break;
+            }
+            grid[(x, y)] = Some(OBSTACLE);
+        }
+    }
+}
+
+/// draw every occupied cell as a point, `color` picks the color of each one
+pub fn paint(
+    ctx: &mut Context,
+    grid: &Grid,
+    playground_width: f64,
+    playground_height: f64,
+    color: impl Fn(usize, usize, u8) -> Color,
+) {
+    let (width, height) = grid.dimensions();
+    for (col, row, val) in grid.iter_cells() {
+        let &Some(val) = val else {
+            continue;
+        };
+        // canvas y goes up, row 0 is the top
+        let y = map_range(
+            (height - 1 - row) as f64,
+            0.0,
+            height as f64,
+            0.0,
+            playground_height,
+        );
+        let x = map_range(col as f64, 0.0, width as f64, 0.0, playground_width);
+        ctx.draw(&Points {
+            coords: &[(x, y)],
+            color: color(col, row, val),
+        });
+    }
+}
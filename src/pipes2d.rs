@@ -0,0 +1,262 @@
+use crate::gamepad;
+use crate::input::{self, Action};
+use crate::utils::{Backoff, RunOptions};
+use color_eyre::Result;
+use oorandom::Rand32;
+use ratatui::{
+    crossterm::event::{self, Event},
+    style::{Color, Stylize},
+    symbols::Marker,
+    text::Span,
+    widgets::{
+        canvas::{Canvas, Line},
+        Paragraph, Widget,
+    },
+    DefaultTerminal, Frame,
+};
+use std::time::{Duration, Instant};
+
+// bitmask of the sides of a cell a pipe connects to
+const UP: u8 = 0b0001;
+const RIGHT: u8 = 0b0010;
+const DOWN: u8 = 0b0100;
+const LEFT: u8 = 0b1000;
+// indexed by direction
+const SIDES: [u8; 4] = [UP, RIGHT, DOWN, LEFT];
+
+fn box_char(sides: u8) -> char {
+    match sides {
+        s if s == UP | DOWN => '┃',
+        s if s == LEFT | RIGHT => '━',
+        s if s == DOWN | RIGHT => '┏',
+        s if s == DOWN | LEFT => '┓',
+        s if s == UP | RIGHT => '┗',
+        s if s == UP | LEFT => '┛',
+        _ => ' ',
+    }
+}
+
+struct Pipe {
+    x: usize,
+    y: usize,
+    // 0 = up, 1 = right, 2 = down, 3 = left
+    direction: usize,
+    color: u8,
+}
+
+pub struct App {
+    exit: bool,
+    // y = 0 is the bottom row, like the canvas
+    grid: Vec<Vec<Option<(u8, u8)>>>,
+    pipes: Vec<Pipe>,
+    n_segments: u32,
+    debug_text: String,
+    quiet: bool,
+    marker: Option<Marker>,
+    rng: Rand32,
+    max_segments: u32,
+    n_pipes: u16,
+    loop_seed: bool,
+    tick_rate: u64,
+    seed: u64,
+}
+
+impl App {
+    pub fn new(
+        terminal_width: u16,
+        terminal_height: u16,
+        marker: Option<Marker>,
+        max_segments: u32,
+        n_pipes: u16,
+        seed: u64,
+    ) -> Self {
+        let grid = vec![vec![None; terminal_width as usize]; terminal_height as usize];
+        Self {
+            exit: false,
+            grid,
+            pipes: Vec::new(),
+            n_segments: 0,
+            debug_text: String::new(),
+            marker,
+            rng: oorandom::Rand32::new(seed),
+            max_segments,
+            n_pipes,
+            loop_seed: false,
+            quiet: false,
+            tick_rate: 0,
+            seed,
+        }
+    }
+
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        self.loop_seed = options.loop_seed;
+        let tick_rate = Duration::from_millis(tick_rate);
+        let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
+        self.reset();
+
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
+            if event::poll(timeout)? {
+                backoff.reset();
+                match event::read()? {
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
+                    Event::Resize(_columns, _rows) => {
+                        // self.debug_text = format!("{} {}", columns, rows);
+                    }
+                    _ => (),
+                }
+            }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
+
+            if last_tick.elapsed() >= tick_rate {
+                if self.n_segments >= self.max_segments {
+                    self.auto_reset();
+                }
+                self.on_tick();
+                last_tick = Instant::now();
+            }
+        }
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "pipes2d --tick-rate {} --max-segments {} --pipes {} --seed {}",
+            self.tick_rate, self.max_segments, self.n_pipes, self.seed
+        );
+        if let Some(marker) = self.marker {
+            args += &format!(" --marker {marker}");
+        }
+        args
+    }
+
+    /// reset on its own, starting from a new seed with --loop-seed
+    fn auto_reset(&mut self) {
+        if self.loop_seed {
+            self.seed = self.rng.rand_u32() as u64;
+            self.rng = oorandom::Rand32::new(self.seed);
+        }
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        for line in self.grid.iter_mut() {
+            for cell in line.iter_mut() {
+                *cell = None;
+            }
+        }
+        self.n_segments = 0;
+        self.pipes.clear();
+        if self.grid.is_empty() || self.grid[0].is_empty() {
+            return;
+        }
+        let height = self.grid.len() as u32;
+        let width = self.grid[0].len() as u32;
+        for _ in 0..self.n_pipes {
+            self.pipes.push(Pipe {
+                x: self.rng.rand_range(0..width) as usize,
+                y: self.rng.rand_range(0..height) as usize,
+                direction: self.rng.rand_range(0..4) as usize,
+                color: self.rng.rand_range(1..8) as u8,
+            });
+        }
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.exit = true,
+            Action::Reset => self.reset(),
+            _ => (),
+        }
+    }
+
+    fn handle_key_press(&mut self, key: event::KeyEvent) {
+        if let Some(action) = input::map_key(key) {
+            self.apply(action);
+        }
+    }
+
+    fn on_tick(&mut self) {
+        if self.grid.is_empty() || self.grid[0].is_empty() {
+            return;
+        }
+        let height = self.grid.len();
+        let width = self.grid[0].len();
+        for pipe in self.pipes.iter_mut() {
+            let came_from = SIDES[(pipe.direction + 2) % 4];
+            // turn left or right every now and then, never back
+            if self.rng.rand_range(0..8) == 0 {
+                let turn = if self.rng.rand_range(0..2) == 0 { 1 } else { 3 };
+                pipe.direction = (pipe.direction + turn) % 4;
+            }
+            self.grid[pipe.y][pipe.x] = Some((came_from | SIDES[pipe.direction], pipe.color));
+
+            match pipe.direction {
+                0 => pipe.y = (pipe.y + 1) % height,
+                1 => pipe.x = (pipe.x + 1) % width,
+                2 => pipe.y = (pipe.y + height - 1) % height,
+                3 => pipe.x = (pipe.x + width - 1) % width,
+                _ => unreachable!(),
+            }
+            self.n_segments += 1;
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self.canvas(), frame.area());
+        if !self.quiet && !self.debug_text.is_empty() {
+            let debug_text = Paragraph::new(self.debug_text.clone());
+            frame.render_widget(debug_text, frame.area());
+        }
+    }
+
+    fn canvas(&self) -> impl Widget + '_ {
+        let height = self.grid.len();
+        let width = self.grid.first().map_or(0, |line| line.len());
+        Canvas::default()
+            .marker(self.marker.unwrap_or(Marker::Braille))
+            .paint(move |ctx| {
+                for (y, line) in self.grid.iter().enumerate() {
+                    for (x, cell) in line.iter().enumerate() {
+                        let Some((sides, color)) = *cell else {
+                            continue;
+                        };
+                        let (x, y) = (x as f64, y as f64);
+                        if self.marker.is_none() {
+                            let span = Span::from(box_char(sides).to_string());
+                            ctx.print(x, y, span.fg(Color::Indexed(color)));
+                            continue;
+                        }
+                        // half a cell from the center towards each connected side
+                        for (side, (dx, dy)) in
+                            SIDES
+                                .iter()
+                                .zip([(0.0, 0.5), (0.5, 0.0), (0.0, -0.5), (-0.5, 0.0)])
+                        {
+                            if sides & side != 0 {
+                                ctx.draw(&Line::new(x, y, x + dx, y + dy, Color::Indexed(color)));
+                            }
+                        }
+                    }
+                }
+            })
+            .x_bounds([0.0, width.saturating_sub(1) as f64])
+            .y_bounds([0.0, height.saturating_sub(1) as f64])
+    }
+}
@@ -1,64 +1,103 @@
-use crate::utils::{is_quit_key, map_range};
+use crate::gamepad;
+use crate::input::{self, Action};
+use crate::utils::{
+    calculate_hash, map_range, sort_back_to_front, value_enum_name, Backoff, RunOptions, Scene,
+    ToScreenPos, FOV_MAX, FOV_MIN,
+};
+use clap::ValueEnum;
 use color_eyre::Result;
 use crossterm::event::KeyEventKind;
-use glam::{DVec2, DVec3};
+use glam::{DQuat, DVec2, DVec3};
+use oorandom::Rand32;
 use ratatui::{
     crossterm::event::{self, Event, KeyCode},
     layout::Rect,
     style::Color,
     symbols::Marker,
-    widgets::{
-        canvas::{Canvas, Line},
-        Paragraph, Widget,
-    },
+    widgets::{canvas::Canvas, Paragraph, Widget},
     DefaultTerminal, Frame,
 };
 use std::time::{Duration, Instant};
 
-trait ToScreenPos {
-    fn to_screen_position(self, playgrground: Rect, val: f64) -> DVec2;
-    fn to_screen_position_orthographic(self, playgrground: Rect) -> DVec2;
-}
+// free camera speed added per key press, and how much of it is left after a tick
+const FREE_CAM_ACCELERATION: f64 = 0.15;
+const FREE_CAM_DAMPING: f64 = 0.85;
+const FREE_CAM_TURN: f64 = 0.05;
+// change of --camera-speed per [ or ] press
+const CAMERA_SPEED_STEP: f64 = 0.5;
 
-impl ToScreenPos for DVec3 {
-    fn to_screen_position(self, playground: Rect, val: f64) -> DVec2 {
-        let z = self.z + 10.0;
-        let x = (self.x) / (val * z);
-        let y = (self.y) / (val * z);
+/// directions a pipe can grow in, the opposite of `i` is `(i + 3) % 6`
+const DIRECTIONS: [DVec3; 6] = [
+    DVec3::new(1.0, 0.0, 0.0),
+    DVec3::new(0.0, 1.0, 0.0),
+    DVec3::new(0.0, 0.0, 1.0),
+    DVec3::new(-1.0, 0.0, 0.0),
+    DVec3::new(0.0, -1.0, 0.0),
+    DVec3::new(0.0, 0.0, -1.0),
+];
 
-        DVec2 {
-            x: x + playground.right() as f64 * 0.5,
-            y: y + playground.bottom() as f64 * 0.5,
-        }
-    }
+/// index into DIRECTIONS to grow in after `previous`.
+/// the offset from `previous` is 4, 5, 0 or 1 (mod 6), never 3,
+/// so a pipe can go straight or turn but never double back on itself
+fn next_direction(previous: usize, rng: &mut Rand32) -> usize {
+    turn(previous, rng.rand_range(1..5))
+}
 
-    fn to_screen_position_orthographic(self, playground: Rect) -> DVec2 {
-        let x = 20.0 * self.x + 0.4 * self.z * 20.0;
-        let y = 20.0 * self.y + 0.4 * self.z * 20.0;
+// next_direction for a given roll in 1..5
+fn turn(previous: usize, roll: u32) -> usize {
+    (previous + 3 + roll as usize) % DIRECTIONS.len()
+}
 
-        DVec2 {
-            x: x + playground.right() as f64 * 0.5,
-            y: y + playground.bottom() as f64 * 0.5,
-        }
-    }
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// One color per straight run, changing at turns
+    #[default]
+    Run,
+    /// Fade from dark at the tail to bright at the head
+    Gradient,
+    /// Cycle colors by segment index
+    Position,
 }
 
 pub struct App {
     exit: bool,
     points: Vec<DVec3>,
+    // color of the segment starting at the point with the same index
+    colors: Vec<u8>,
+    run_color: u8,
+    color_mode: ColorMode,
     playground: Rect,
     tick_count: u64,
     camera_position: DVec3,
     previous_index: usize,
     debug_text: String,
+    quiet: bool,
     marker: Marker,
     max_segments: u32,
     orthographic: bool,
     val: f64,
+    fov_step: f64,
     rotate: bool,
+    anaglyph: bool,
+    eye_separation: f64,
+    free_cam: bool,
+    camera_velocity: DVec3,
+    // rotation of the look direction around the y axis
+    camera_yaw: f64,
+    thickness: u8,
+    z_sort: bool,
+    // only redraw when the projected scene changes
+    skip_unchanged: bool,
+    tick_rate: u64,
+    seed: u64,
+    rng: Rand32,
+    // where the next segment starts
+    current_point: DVec3,
+    camera_speed: f64,
 }
 
 impl App {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         terminal_width: u16,
         terminal_height: u16,
@@ -66,6 +105,15 @@ impl App {
         max_segments: u32,
         orthographic: bool,
         rotate: bool,
+        fov: f64,
+        fov_step: f64,
+        color_mode: ColorMode,
+        anaglyph: bool,
+        eye_separation: f64,
+        free_cam: bool,
+        thickness: u8,
+        z_sort: bool,
+        skip_unchanged: bool,
     ) -> Self {
         let scale_factor = terminal_height as f32 / terminal_width as f32;
         let font_scale_factor = 2.0;
@@ -75,6 +123,9 @@ impl App {
             exit: false,
             playground: Rect::new(0, 0, width as u16, height as u16),
             points: Vec::with_capacity(max_segments as usize),
+            colors: Vec::with_capacity(max_segments as usize),
+            run_color: 1,
+            color_mode,
             tick_count: 0,
             camera_position: DVec3::default(),
             marker,
@@ -82,8 +133,23 @@ impl App {
             previous_index: 0,
             max_segments,
             orthographic,
-            val: 0.01,
+            val: fov.clamp(FOV_MIN, FOV_MAX),
+            fov_step,
             rotate,
+            anaglyph,
+            eye_separation,
+            free_cam,
+            camera_velocity: DVec3::ZERO,
+            camera_yaw: 0.0,
+            thickness,
+            z_sort,
+            skip_unchanged,
+            quiet: false,
+            tick_rate: 0,
+            seed: 0,
+            rng: Rand32::new(0),
+            current_point: DVec3::ZERO,
+            camera_speed: 0.0,
         }
     }
 
@@ -93,38 +159,62 @@ impl App {
         tick_rate: u64,
         seed: u64,
         camera_speed: f64,
-    ) -> Result<()> {
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        self.seed = seed;
+        self.rng = Rand32::new(seed);
+        self.camera_speed = camera_speed;
         let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
-        let mut rng = oorandom::Rand32::new(seed);
-        let mut current_point = DVec3 {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        };
-        let follow_speed = map_range(camera_speed, 0.0, 10.0, 0.0, 0.01).clamp(0.0, 1.0);
+        let mut backoff = Backoff::new(options.adaptive);
+        // --dump-frames wants every frame, changed or not
+        let skip_unchanged = self.skip_unchanged && options.frame_dumper.is_none();
+        let mut last_scene_hash = None;
 
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            let scene = self.scene();
+            let scene_hash = calculate_hash(&(scene.hash(), &self.debug_text));
+            if !skip_unchanged || last_scene_hash != Some(scene_hash) {
+                options.draw(&mut terminal, &mut backoff, |frame| {
+                    self.draw(frame, &scene)
+                })?;
+                last_scene_hash = Some(scene_hash);
+            }
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
                     Event::Resize(_columns, _rows) => {
-                        // self.debug_text = format!("{} {}", columns, rows);
+                        // the same scene still has to fill the new size
+                        last_scene_hash = None;
                     }
                     _ => (),
                 }
             }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
 
             if last_tick.elapsed() >= tick_rate {
                 if self.points.len() as u32 >= self.max_segments {
                     if self.rotate {
                         self.points.rotate_left(1);
                         self.points.pop();
+                        self.colors.rotate_left(1);
+                        self.colors.pop();
                     } else {
+                        if options.loop_seed {
+                            self.seed = self.rng.rand_u32() as u64;
+                            self.rng = Rand32::new(self.seed);
+                        }
                         self.reset();
-                        current_point = DVec3::default();
                     }
                 }
                 let last_point = if self.points.is_empty() {
@@ -133,91 +223,241 @@ impl App {
                     *self.points.last().unwrap()
                 };
 
-                let direction = last_point - self.camera_position;
-                self.camera_position += direction * follow_speed;
+                if self.free_cam {
+                    self.camera_position += self.camera_velocity;
+                    self.camera_velocity *= FREE_CAM_DAMPING;
+                } else {
+                    let direction = last_point - self.camera_position;
+                    let follow_speed =
+                        map_range(self.camera_speed, 0.0, 10.0, 0.0, 0.01).clamp(0.0, 1.0);
+                    self.camera_position += direction * follow_speed;
+                }
                 self.on_tick();
                 last_tick = Instant::now();
                 if self.tick_count.is_multiple_of(2)
                     && (self.points.len() as u32) < self.max_segments
                 {
-                    self.points.push(current_point);
-                    let unit_vectors = [
-                        DVec3::new(1.0, 0.0, 0.0),
-                        DVec3::new(0.0, 1.0, 0.0),
-                        DVec3::new(0.0, 0.0, 1.0),
-                        DVec3::new(-1.0, 0.0, 0.0),
-                        DVec3::new(0.0, -1.0, 0.0),
-                        DVec3::new(0.0, 0.0, -1.0),
-                    ];
-                    let n = (self.previous_index + 3 + rng.rand_range(1..5) as usize) % 6;
+                    self.points.push(self.current_point);
+                    let n = next_direction(self.previous_index, &mut self.rng);
+                    if n != self.previous_index {
+                        self.run_color = self.run_color % 7 + 1;
+                    }
+                    self.colors.push(self.run_color);
                     self.previous_index = n;
-                    current_point += unit_vectors[n];
+                    self.current_point += DIRECTIONS[n];
                 }
             }
         }
-        Ok(())
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "pipes3d --marker {} --max-segments {} --tick-rate {} --seed {} --camera-speed {} --fov {} --fov-step {} --color-mode {} --eye-separation {}",
+            self.marker,
+            self.max_segments,
+            self.tick_rate,
+            self.seed,
+            self.camera_speed,
+            self.val,
+            self.fov_step,
+            value_enum_name(self.color_mode),
+            self.eye_separation
+        );
+        if self.orthographic {
+            args += " --orthographic";
+        }
+        if self.rotate {
+            args += " --rotate";
+        }
+        if self.anaglyph {
+            args += " --anaglyph";
+        }
+        if self.free_cam {
+            args += " --free-cam";
+        }
+        if self.thickness > 1 {
+            args += &format!(" --thickness {}", self.thickness);
+        }
+        if self.z_sort {
+            args += " --z-sort";
+        }
+        if self.skip_unchanged {
+            args += " --skip-unchanged";
+        }
+        args
     }
 
     fn reset(&mut self) {
         self.points.clear();
+        self.colors.clear();
         self.camera_position = DVec3::default();
+        self.current_point = DVec3::default();
+        // a fresh pipe doesn't carry over the old one's direction
+        self.previous_index = 0;
+    }
+
+    /// start over from `seed`, n and N step through seeds
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rand32::new(seed);
+        self.reset();
+        self.debug_text = format!("seed: {seed}");
+    }
+
+    fn adjust_fov(&mut self, delta: f64) {
+        self.val = (self.val + delta).clamp(FOV_MIN, FOV_MAX);
+        self.debug_text = format!("FOV: {:.3}", self.val);
+    }
+
+    fn adjust_camera_speed(&mut self, delta: f64) {
+        self.camera_speed = (self.camera_speed + delta).max(0.0);
+        self.debug_text = format!("camera speed: {:.1}", self.camera_speed);
+    }
+
+    fn apply(&mut self, action: Action) {
+        let forward = DQuat::from_rotation_y(self.camera_yaw) * DVec3::Z;
+        let right = DQuat::from_rotation_y(self.camera_yaw) * DVec3::X;
+        match action {
+            Action::Quit => self.exit = true,
+            // zooming in means a smaller fov
+            Action::ZoomIn => self.adjust_fov(-self.fov_step),
+            Action::ZoomOut => self.adjust_fov(self.fov_step),
+            Action::NextSeed => self.set_seed(self.seed.wrapping_add(1)),
+            Action::PrevSeed => self.set_seed(self.seed.wrapping_sub(1)),
+            Action::PanUp if self.free_cam => self.push_camera(forward),
+            Action::PanDown if self.free_cam => self.push_camera(-forward),
+            Action::PanLeft if self.free_cam => self.push_camera(-right),
+            Action::PanRight if self.free_cam => self.push_camera(right),
+            Action::TurnLeft if self.free_cam => self.camera_yaw -= FREE_CAM_TURN,
+            Action::TurnRight if self.free_cam => self.camera_yaw += FREE_CAM_TURN,
+            _ => (),
+        }
     }
 
     fn handle_key_press(&mut self, key: event::KeyEvent) {
+        // before the shared bindings, a and d move instead of zooming and q goes down
+        if self.free_cam && key.kind == KeyEventKind::Press && self.handle_free_cam_key(key.code) {
+            return;
+        }
+        if let Some(action) = input::map_key(key) {
+            self.apply(action);
+            return;
+        }
         if key.kind != KeyEventKind::Press {
             return;
         }
         match key.code {
-            KeyCode::Char('a') => self.val += 0.001,
-            KeyCode::Char('d') => self.val -= 0.001,
-            _ => {
-                if is_quit_key(key) {
-                    self.exit = true;
-                }
-            }
+            KeyCode::Char('[') => self.adjust_camera_speed(-CAMERA_SPEED_STEP),
+            KeyCode::Char(']') => self.adjust_camera_speed(CAMERA_SPEED_STEP),
+            _ => (),
         }
     }
 
+    /// move or turn the free camera, false if the key isn't a camera key
+    fn handle_free_cam_key(&mut self, code: KeyCode) -> bool {
+        let action = match code {
+            KeyCode::Char('w') | KeyCode::Up => Action::PanUp,
+            KeyCode::Char('s') | KeyCode::Down => Action::PanDown,
+            KeyCode::Char('a') => Action::PanLeft,
+            KeyCode::Char('d') => Action::PanRight,
+            KeyCode::Left => Action::TurnLeft,
+            KeyCode::Right => Action::TurnRight,
+            KeyCode::Char('e') => {
+                self.push_camera(DVec3::Y);
+                return true;
+            }
+            KeyCode::Char('q') => {
+                self.push_camera(-DVec3::Y);
+                return true;
+            }
+            _ => return false,
+        };
+        self.apply(action);
+        true
+    }
+
+    /// speed the free camera up towards `direction`
+    fn push_camera(&mut self, direction: DVec3) {
+        self.camera_velocity += direction * FREE_CAM_ACCELERATION;
+    }
+
     fn on_tick(&mut self) {
         self.tick_count += 1;
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+    fn draw(&self, frame: &mut Frame, scene: &Scene) {
+        frame.render_widget(self.canvas(scene), frame.area());
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
     }
 
-    fn canvas(&self) -> impl Widget + '_ {
-        Canvas::default()
-            .marker(self.marker)
-            .paint(|ctx| {
-                'outer: for (i, win) in self.points.windows(2).enumerate() {
-                    let mut line_points: [DVec2; 2] = [DVec2::ZERO; 2];
-                    let index_f = i as f64 * 0.1;
-                    let color_index = ((index_f as u64 % 7) + 1) as u8;
-                    for (i, point) in win.iter().enumerate() {
-                        let modified_point = *point - self.camera_position;
-                        if modified_point.z < -9.0 && !self.orthographic {
-                            continue 'outer;
-                        }
-                        if self.orthographic {
-                            line_points[i] =
-                                modified_point.to_screen_position_orthographic(self.playground);
-                        } else {
-                            line_points[i] =
-                                modified_point.to_screen_position(self.playground, self.val);
-                        }
-                    }
+    fn segment_color(&self, i: usize) -> Color {
+        match self.color_mode {
+            ColorMode::Run => Color::Indexed(self.colors[i]),
+            ColorMode::Gradient => {
+                // grayscale ramp 232..=255
+                let n_segments = self.points.len().max(2) - 1;
+                Color::Indexed(232 + (23 * i / n_segments) as u8)
+            }
+            ColorMode::Position => {
+                let index_f = i as f64 * 0.1;
+                Color::Indexed(((index_f as u64 % 7) + 1) as u8)
+            }
+        }
+    }
+
+    /// `point` relative to the camera
+    fn project(&self, point: DVec3) -> DVec2 {
+        if self.orthographic {
+            point.to_screen_position_orthographic(self.playground, 20.0)
+        } else {
+            (point + 10.0 * DVec3::Z).to_screen_position(self.playground, self.val)
+        }
+    }
 
-                    let p0 = line_points[0];
-                    let p1 = line_points[1];
-                    let line = Line::new(p0.x, p0.y, p1.x, p1.y, Color::Indexed(color_index));
-                    ctx.draw(&line);
+    /// the visible segments projected to the canvas
+    fn scene(&self) -> Scene {
+        let mut segments = Vec::with_capacity(self.points.len());
+        for (i, win) in self.points.windows(2).enumerate() {
+            // into camera space, looking down +z
+            let view = DQuat::from_rotation_y(-self.camera_yaw);
+            let p0 = view * (win[0] - self.camera_position);
+            let p1 = view * (win[1] - self.camera_position);
+            if (p0.z < -9.0 || p1.z < -9.0) && !self.orthographic {
+                continue;
+            }
+            segments.push((p0, p1, i));
+        }
+        if self.z_sort {
+            sort_back_to_front(&mut segments);
+        }
+        let mut scene = Scene::default();
+        for (p0, p1, i) in segments {
+            if self.anaglyph {
+                // left eye in red, right eye in cyan
+                let offset = 0.5 * self.eye_separation * DVec3::X;
+                for (offset, color) in [(offset, Color::Red), (-offset, Color::Cyan)] {
+                    let s0 = self.project(p0 + offset);
+                    let s1 = self.project(p1 + offset);
+                    scene.lines.push((s0, s1, color));
                 }
-            })
+            } else {
+                let s0 = self.project(p0);
+                let s1 = self.project(p1);
+                scene.lines.push((s0, s1, self.segment_color(i)));
+            }
+        }
+        scene
+    }
+
+    fn canvas<'a>(&'a self, scene: &'a Scene) -> impl Widget + 'a {
+        Canvas::default()
+            .marker(self.marker)
+            .paint(|ctx| scene.paint(ctx, self.thickness))
             .x_bounds([
                 self.playground.left() as f64,
                 self.playground.right() as f64,
@@ -228,3 +468,30 @@ impl App {
             ])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_doubles_back() {
+        for (previous, direction) in DIRECTIONS.iter().enumerate() {
+            let opposite = (previous + 3) % DIRECTIONS.len();
+            assert_eq!(DIRECTIONS[opposite], -*direction);
+            for roll in 1..5 {
+                assert_ne!(turn(previous, roll), opposite);
+            }
+        }
+    }
+
+    #[test]
+    fn next_direction_never_doubles_back() {
+        let mut rng = Rand32::new(7);
+        for previous in (0..DIRECTIONS.len()).cycle().take(6000) {
+            assert_ne!(
+                next_direction(previous, &mut rng),
+                (previous + 3) % DIRECTIONS.len()
+            );
+        }
+    }
+}
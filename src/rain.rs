@@ -0,0 +1,319 @@
+use crate::gamepad;
+use crate::input::{self, Action};
+use crate::particle_grid::{self, Grid, OBSTACLE};
+use crate::utils::{Backoff, RunOptions};
+use color_eyre::Result;
+use glam::DVec2;
+use oorandom::Rand64;
+use ratatui::{
+    crossterm::event::{self, Event},
+    style::Color,
+    symbols::Marker,
+    widgets::{canvas::Canvas, Paragraph, Widget},
+    DefaultTerminal, Frame,
+};
+use std::time::{Duration, Instant};
+
+const WATER: u8 = 2;
+// ticks a water cell has to sit still before it counts as a puddle
+const SETTLE_TICKS: u16 = 4;
+// puddle surface starts evaporating after this many ticks
+const EVAPORATE_TICKS: u16 = 600;
+
+pub struct App {
+    grid: Grid,
+    // ticks each water cell hasn't moved for
+    still_ticks: Vec<Vec<u16>>,
+    exit: bool,
+    playground: DVec2,
+    debug_text: String,
+    quiet: bool,
+    marker: Marker,
+    rng: Rand64,
+    intensity: f64,
+    wind: f64,
+    obstacles: usize,
+    tick_rate: u64,
+    seed: u128,
+}
+
+impl App {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        terminal_width: u16,
+        terminal_height: u16,
+        marker: Marker,
+        intensity: f64,
+        wind: f64,
+        obstacles: usize,
+        seed: u128,
+    ) -> Self {
+        let grid = particle_grid::new_grid(marker, terminal_width, terminal_height);
+        let (board_width, board_height) = grid.dimensions();
+
+        Self {
+            grid,
+            still_ticks: vec![vec![0; board_width]; board_height],
+            exit: false,
+            playground: DVec2::new(board_width as f64, board_height as f64),
+            marker,
+            debug_text: String::new(),
+            rng: oorandom::Rand64::new(seed),
+            intensity,
+            wind,
+            obstacles,
+            quiet: false,
+            tick_rate: 0,
+            seed,
+        }
+    }
+
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        let tick_rate = Duration::from_millis(tick_rate);
+        let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
+        self.reset();
+
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
+            if event::poll(timeout)? {
+                backoff.reset();
+                match event::read()? {
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
+                    Event::Resize(_columns, _rows) => {
+                        // self.debug_text = format!("{} {}", columns, rows);
+                    }
+                    _ => (),
+                }
+            }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
+
+            if last_tick.elapsed() >= tick_rate {
+                last_tick = Instant::now();
+                self.spawn();
+                self.on_tick();
+            }
+        }
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        format!(
+            "rain --marker {} --tick-rate {} --intensity {} --wind {} --obstacles {} --seed {}",
+            self.marker, self.tick_rate, self.intensity, self.wind, self.obstacles, self.seed
+        )
+    }
+
+    fn reset(&mut self) {
+        self.grid.fill(None);
+        particle_grid::place_obstacles(&mut self.grid, &mut self.rng, self.obstacles, 12);
+    }
+
+    /// spawn on average `intensity` drops per tick along the top row
+    fn spawn(&mut self) {
+        let (width, _) = self.grid.dimensions();
+        if width == 0 {
+            return;
+        }
+        let mut n = self.intensity.trunc() as usize;
+        if self.rng.rand_float() < self.intensity.fract() {
+            n += 1;
+        }
+        for _ in 0..n {
+            let x = self.rng.rand_range(0..width as u64) as usize;
+            if self.grid[(x, 0)].is_none() {
+                self.grid[(x, 0)] = Some(WATER);
+                self.still_ticks[0][x] = 0;
+            }
+        }
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.exit = true,
+            Action::Reset => self.reset(),
+            _ => (),
+        }
+    }
+
+    fn handle_key_press(&mut self, key: event::KeyEvent) {
+        if let Some(action) = input::map_key(key) {
+            self.apply(action);
+        }
+    }
+
+    fn move_water(&mut self, from: (usize, usize), to: (usize, usize)) {
+        self.grid[(to.0, to.1)] = self.grid[(from.0, from.1)].take();
+        self.still_ticks[to.1][to.0] = 0;
+    }
+
+    fn is_empty(&self, x: usize, y: usize) -> bool {
+        self.grid[(x, y)].is_none()
+    }
+
+    fn on_tick(&mut self) {
+        let (width, height) = self.grid.dimensions();
+        if width < 3 || height < 2 {
+            return;
+        }
+        let mut moved = vec![vec![false; width]; height];
+
+        // bottom up so a drop only falls one cell per tick
+        for y in (0..height).rev() {
+            // alternate sweep direction so water doesn't drift to one side
+            let xs: Vec<usize> = if self.rng.rand_range(0..2) == 0 {
+                (0..width).collect()
+            } else {
+                (0..width).rev().collect()
+            };
+            for x in xs {
+                if self.grid[(x, y)] != Some(WATER) || moved[y][x] {
+                    continue;
+                }
+                let falling = self.still_ticks[y][x] < SETTLE_TICKS;
+                let mut target = None;
+
+                if y + 1 < height && self.is_empty(x, y + 1) {
+                    target = Some((x, y + 1));
+                    // wind only pushes drops that are still in the air
+                    if falling && self.rng.rand_float() < self.wind.abs() {
+                        let drift_x = if self.wind > 0.0 {
+                            (x + 1).min(width - 1)
+                        } else {
+                            x.saturating_sub(1)
+                        };
+                        if self.is_empty(drift_x, y + 1) {
+                            target = Some((drift_x, y + 1));
+                        }
+                    }
+                } else if y > 0
+                    && y + 1 < height
+                    && self.grid[(x, y + 1)] == Some(OBSTACLE)
+                    && falling
+                    && self.rng.rand_range(0..2) == 0
+                {
+                    // splash: bounce up and to the side off the obstacle
+                    let splash_x = if self.rng.rand_range(0..2) == 0 {
+                        x.saturating_sub(1)
+                    } else {
+                        (x + 1).min(width - 1)
+                    };
+                    if self.is_empty(splash_x, y - 1) {
+                        target = Some((splash_x, y - 1));
+                    }
+                } else {
+                    let dir: i32 = if self.rng.rand_range(0..2) == 0 {
+                        -1
+                    } else {
+                        1
+                    };
+                    'find: for dx in [dir, -dir] {
+                        let side_x = x as i32 + dx;
+                        if side_x < 0 || side_x >= width as i32 {
+                            continue;
+                        }
+                        let side_x = side_x as usize;
+                        // diagonally down first, then flow sideways
+                        if y + 1 < height && self.is_empty(side_x, y + 1) {
+                            target = Some((side_x, y + 1));
+                            break 'find;
+                        }
+                        if self.is_empty(side_x, y) {
+                            target = Some((side_x, y));
+                            break 'find;
+                        }
+                    }
+                }
+
+                match target {
+                    Some(to) => {
+                        self.move_water((x, y), to);
+                        moved[to.1][to.0] = true;
+                    }
+                    None => {
+                        self.still_ticks[y][x] = self.still_ticks[y][x].saturating_add(1);
+                        let is_surface = y == 0 || self.grid[(x, y - 1)].is_none();
+                        if is_surface
+                            && self.still_ticks[y][x] > EVAPORATE_TICKS
+                            && self.rng.rand_range(0..50) == 0
+                        {
+                            self.grid[(x, y)] = None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self.canvas(), frame.area());
+        if !self.quiet && !self.debug_text.is_empty() {
+            let debug_text = Paragraph::new(self.debug_text.clone());
+            frame.render_widget(debug_text, frame.area());
+        }
+    }
+
+    fn canvas(&self) -> impl Widget + '_ {
+        Canvas::default()
+            .marker(self.marker)
+            .paint(|ctx| {
+                particle_grid::paint(
+                    ctx,
+                    &self.grid,
+                    self.playground.x,
+                    self.playground.y,
+                    |x, y, val| {
+                        if val == OBSTACLE {
+                            Color::Indexed(8)
+                        } else if self.still_ticks[y][x] < SETTLE_TICKS {
+                            Color::Indexed(14)
+                        } else {
+                            Color::Indexed(4)
+                        }
+                    },
+                );
+            })
+            .x_bounds([0.0, self.playground.x])
+            .y_bounds([0.0, self.playground.y])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot;
+
+    #[test]
+    fn puddles_match_snapshot() {
+        let mut app = App::new(60, 20, Marker::HalfBlock, 1.0, 0.3, 20, 4);
+        app.reset();
+        for _ in 0..400 {
+            app.spawn();
+            app.on_tick();
+        }
+        let (width, height) = app.grid.dimensions();
+        let mut board = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                board.push(snapshot::cell_char(app.grid[(x, y)], Some(OBSTACLE)));
+            }
+            board.push('\n');
+        }
+        snapshot::assert_snapshot("rain", &board);
+    }
+}
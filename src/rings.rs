@@ -1,9 +1,11 @@
-use crate::utils::is_quit_key;
+use crate::input::{self, Action};
+use crate::utils::{Backoff, RunOptions, ToScreenPos};
 use color_eyre::Result;
-use crossterm::event::KeyEventKind;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
 use glam::{dvec3, DVec2, DVec3};
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode},
+    crossterm::event::{self, Event},
     layout::Rect,
     style::Color,
     symbols::Marker,
@@ -27,34 +29,6 @@ impl ConvertAngle for f64 {
     }
 }
 
-trait ToScreenPos {
-    fn to_screen_position(self, playgrground: Rect, val: f64) -> DVec2;
-    fn to_screen_position_orthographic(self, playgrground: Rect) -> DVec2;
-}
-
-impl ToScreenPos for DVec3 {
-    fn to_screen_position(self, playground: Rect, val: f64) -> DVec2 {
-        let z = self.z;
-        let x = (self.x) / (val * z);
-        let y = (self.y) / (val * z);
-
-        DVec2 {
-            x: x + playground.right() as f64 * 0.5,
-            y: y + playground.bottom() as f64 * 0.5,
-        }
-    }
-
-    fn to_screen_position_orthographic(self, playground: Rect) -> DVec2 {
-        let x = 3.0 * self.x + 0.4 * self.z * 3.0;
-        let y = 3.0 * self.y + 0.4 * self.z * 3.0;
-
-        DVec2 {
-            x: x + playground.right() as f64 * 0.5,
-            y: y + playground.bottom() as f64 * 0.5,
-        }
-    }
-}
-
 fn rotate_z(point: DVec3, angle: f64) -> DVec3 {
     let s = angle.sin();
     let c = angle.cos();
@@ -82,8 +56,10 @@ fn rotate_y(point: DVec3, angle: f64) -> DVec3 {
 pub struct App {
     exit: bool,
     playground: Rect,
-    tick_count: u64,
+    // animation time, so the speed doesn't depend on the tick rate
+    elapsed_secs: f64,
     debug_text: String,
+    quiet: bool,
     marker: Marker,
     orthographic: bool,
     val: f64,
@@ -95,6 +71,7 @@ pub struct App {
     frequency: f64,
     speed: f64,
     color_speed: Option<f64>,
+    tick_rate: u64,
 }
 
 impl App {
@@ -133,7 +110,7 @@ impl App {
         Self {
             exit: false,
             playground: Rect::new(0, 0, width as u16, height as u16),
-            tick_count: 0,
+            elapsed_secs: 0.0,
             marker,
             debug_text: String::new(),
             orthographic,
@@ -146,19 +123,44 @@ impl App {
             frequency,
             speed,
             color_speed: None,
+            quiet: false,
+            tick_rate: 0,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal, tick_rate: u64) -> Result<()> {
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
         let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
+        // for zooming with the scroll wheel
+        execute!(std::io::stdout(), EnableMouseCapture)?;
 
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
+                    Event::Key(key) if !options.skip_turn(key) => {
+                        if let Some(action) = input::map_key(key) {
+                            self.apply(action);
+                        }
+                    }
+                    Event::Mouse(mouse) => {
+                        if let Some(action) = input::map_mouse(mouse) {
+                            self.apply(action);
+                        }
+                    }
                     Event::Resize(_columns, _rows) => {
                         // self.debug_text = format!("{} {}", columns, rows);
                     }
@@ -167,31 +169,47 @@ impl App {
             }
 
             if last_tick.elapsed() >= tick_rate {
+                self.elapsed_secs += last_tick.elapsed().as_secs_f64();
                 last_tick = Instant::now();
-                self.tick_count += 1;
             }
         }
-        Ok(())
+        execute!(std::io::stdout(), DisableMouseCapture)?;
+        Ok(self)
     }
 
-    fn handle_key_press(&mut self, key: event::KeyEvent) {
-        if key.kind != KeyEventKind::Press {
-            return;
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "rings --marker {} --tick-rate {} --x-rotation-speed {} --y-rotation-speed {} --z-rotation-speed {} --amplitude {} --frequency {} --speed {} --zoom {}",
+            self.marker,
+            self.tick_rate,
+            self.x_rotation_speed,
+            self.y_rotation_speed,
+            self.z_rotation_speed,
+            self.amplitude,
+            self.frequency,
+            self.speed,
+            // inverse of the zoom to fov mapping in new
+            (0.01 - self.val) / 0.001
+        );
+        if self.orthographic {
+            args += " --orthographic";
         }
-        match key.code {
-            KeyCode::Char('a') => self.val += 0.001,
-            KeyCode::Char('d') => self.val -= 0.001,
-            _ => {
-                if is_quit_key(key) {
-                    self.exit = true;
-                }
-            }
+        args
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::ZoomOut => self.val += 0.001,
+            Action::ZoomIn => self.val -= 0.001,
+            Action::Quit => self.exit = true,
+            _ => (),
         }
     }
 
     fn draw(&self, frame: &mut Frame) {
         frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
@@ -201,7 +219,7 @@ impl App {
         Canvas::default()
             .marker(self.marker)
             .paint(|ctx| {
-                let t = self.tick_count as f64 * 0.01;
+                let t = self.elapsed_secs * 1.25;
                 let mut c: u16 = 0;
                 for win in self.points.windows(2) {
                     let mut line_points: [DVec2; 2] = [DVec2::ZERO; 2];
@@ -215,8 +233,8 @@ impl App {
                         modified_point = rotate_z(modified_point, t * self.z_rotation_speed);
 
                         if self.orthographic {
-                            line_points[i] =
-                                modified_point.to_screen_position_orthographic(self.playground);
+                            line_points[i] = modified_point
+                                .to_screen_position_orthographic(self.playground, 3.0);
                         } else {
                             modified_point += 30.0 * DVec3::Z;
                             line_points[i] =
@@ -1,4 +1,11 @@
-use crate::utils::{calc_board_size_scaled, calculate_hash, is_quit_key, map_range};
+use crate::gamepad;
+use crate::glyphs::GlyphSet;
+use crate::input::{self, Action};
+use crate::particle_grid::{self, Grid, OBSTACLE};
+use crate::utils::{
+    draw_ascii, indexed_to_rgb, next_seed, rgb_to_indexed, value_enum_name, Backoff, RunOptions,
+};
+use clap::ValueEnum;
 use color_eyre::Result;
 use crossterm::event::KeyEventKind;
 use glam::DVec2;
@@ -7,33 +14,139 @@ use ratatui::{
     crossterm::event::{self, Event, KeyCode},
     style::Color,
     symbols::Marker,
-    widgets::{
-        canvas::{Canvas, Points},
-        Paragraph, Widget,
-    },
+    widgets::{canvas::Canvas, Paragraph, Widget},
     DefaultTerminal, Frame,
 };
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
+pub use crate::particle_grid::CellState;
+
+// columns a sweeping emitter moves per spawned particle
+const SWEEP_SPEED: f64 = 0.3;
+// (cells between lattice points, weight) of each --terrain noise octave
+const TERRAIN_OCTAVES: [(usize, f64); 2] = [(24, 0.7), (8, 0.3)];
+// colors listed with --stats, most common first
+const STATS_COLORS: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum EmitterMode {
+    /// Emitters stay in place and jump to a new column now and then
+    #[default]
+    Point,
+    /// Emitters move back and forth across the top
+    Sweep,
+    /// Every particle drops from a random column of the top row
+    FullWidth,
+}
+
+/// what the pile is doing, moves on from spawning to emptying to resetting and back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    /// emitters drop grains until one of them is buried
+    Spawning,
+    /// the floor is open and the pile drains out
+    Emptying,
+    /// emptied --reset times, the next step starts over with new obstacles
+    Resetting,
+    /// an emitter got buried with --no-reset, the pile stays as it is
+    Full,
+}
+
+struct Emitter {
+    x: f64,
+    // sweep direction, 1 or -1
+    direction: f64,
+    color: u8,
+}
+
 pub struct App {
-    grid: Vec<Vec<Option<u8>>>,
+    grid: Grid,
     exit: bool,
     playground: DVec2,
     debug_text: String,
+    quiet: bool,
     marker: Marker,
     rng: Rand64,
-    spawn_point: usize,
-    color: u8,
+    emitters: Vec<Emitter>,
+    n_emitters: usize,
+    emitter_mode: EmitterMode,
     speed: usize,
     obstacles: usize,
+    // same layout on every reset, otherwise obstacles come from rng
+    obstacle_seed: Option<u128>,
     particles: u64,
+    // grains per emitter per step, and the part of a grain owed from earlier steps
+    spawn_rate: f32,
+    spawn_budget: f32,
     flip_after: Option<u32>,
     obstacle_len: usize,
-    is_emptying: bool,
-    is_spawning: bool,
-    hash_history: Vec<u64>,
+    obstacle_color: u8,
+    // noise terrain density, replaces the line obstacles
+    terrain: Option<f32>,
+    // grains can slide over the top of obstacles
+    burying: bool,
+    phase: Phase,
+    // grains that fell during the last on_tick
+    n_moved: usize,
+    show_stats: bool,
     empties_until_reset: usize,
     empties: usize,
+    // steps while spawning, paces --flip-after
+    step_count: u32,
+    ascii: bool,
+    // sideways bias between -1 (left) and 1 (right)
+    wind: f64,
+    // blend colors of grains landing next to other colors
+    mix: bool,
+    // stop at a full pile instead of emptying and resetting
+    no_reset: bool,
+    loop_seed: bool,
+    tick_rate: u64,
+    seed: u128,
+}
+
+/// fill cells where seeded value noise exceeds `threshold` (0.0 to 1.0) with obstacles,
+/// leaving the top and bottom 10% free for emitters and the draining floor
+fn generate_terrain(grid: &mut Grid, rng: &mut Rand64, threshold: f64) {
+    let (width, height) = grid.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let mut noise = vec![vec![0.0; width]; height];
+    for (spacing, weight) in TERRAIN_OCTAVES {
+        // random values on a coarse lattice, smoothly interpolated in between
+        let lattice_width = width / spacing + 2;
+        let lattice_height = height / spacing + 2;
+        let lattice: Vec<Vec<f64>> = (0..lattice_height)
+            .map(|_| (0..lattice_width).map(|_| rng.rand_float()).collect())
+            .collect();
+        let smoothstep = |t: f64| t * t * (3.0 - 2.0 * t);
+        for (y, line) in noise.iter_mut().enumerate() {
+            let (ly, ty) = (
+                y / spacing,
+                smoothstep((y % spacing) as f64 / spacing as f64),
+            );
+            for (x, value) in line.iter_mut().enumerate() {
+                let (lx, tx) = (
+                    x / spacing,
+                    smoothstep((x % spacing) as f64 / spacing as f64),
+                );
+                let top = lattice[ly][lx] + (lattice[ly][lx + 1] - lattice[ly][lx]) * tx;
+                let bottom =
+                    lattice[ly + 1][lx] + (lattice[ly + 1][lx + 1] - lattice[ly + 1][lx]) * tx;
+                *value += weight * (top + (bottom - top) * ty);
+            }
+        }
+    }
+    let rows = (height as f64 * 0.1) as usize..(height as f64 * 0.9) as usize;
+    for y in rows {
+        for x in 0..width {
+            if noise[y][x] > threshold {
+                grid[(x, y)] = Some(OBSTACLE);
+            }
+        }
+    }
 }
 
 impl App {
@@ -43,271 +156,619 @@ impl App {
         terminal_height: u16,
         marker: Marker,
         seed: u128,
+        obstacle_seed: Option<u128>,
         speed: usize,
         obstacles: usize,
         obstacle_len: usize,
+        obstacle_color: u8,
+        terrain: Option<f32>,
+        burying: bool,
         particles: u64,
+        spawn_rate: f32,
         flip_after: Option<u32>,
         empties_until_reset: usize,
+        n_emitters: usize,
+        emitter_mode: EmitterMode,
+        ascii: bool,
+        wind: f64,
+        mix: bool,
+        no_reset: bool,
+        show_stats: bool,
     ) -> Self {
         let rng = oorandom::Rand64::new(seed);
-        let mut grid = Vec::new();
-
-        let (board_width, board_height) =
-            calc_board_size_scaled(marker, terminal_width, terminal_height);
-
-        for _ in 0..board_height {
-            let mut line = Vec::new();
-            line.resize(board_width, None);
-            grid.push(line);
-        }
+        let grid = particle_grid::new_grid(marker, terminal_width, terminal_height);
+        let (board_width, board_height) = grid.dimensions();
+        // more than this would leave no room for the sand to settle
+        let max_obstacles = particle_grid::max_obstacles(&grid);
+        let debug_text = if terrain.is_none() && obstacles > max_obstacles {
+            format!("obstacles capped at {max_obstacles} for this board")
+        } else {
+            String::new()
+        };
 
         Self {
             grid,
             exit: false,
             playground: DVec2::new(board_width as f64, board_height as f64),
             marker,
-            debug_text: String::new(),
+            debug_text,
             rng,
-            // spawn point and color set by initial reset()
-            spawn_point: 0,
-            color: 0,
+            // emitters placed by initial reset()
+            emitters: Vec::new(),
+            n_emitters: n_emitters.max(1),
+            emitter_mode,
             speed,
-            obstacles,
+            obstacles: obstacles.min(max_obstacles),
+            obstacle_seed,
             particles,
+            spawn_rate,
+            spawn_budget: 0.0,
             flip_after,
             obstacle_len,
-            is_emptying: false,
-            is_spawning: true,
-            hash_history: Vec::new(),
+            obstacle_color,
+            terrain,
+            burying,
+            phase: Phase::Spawning,
+            n_moved: 0,
+            show_stats,
             empties_until_reset,
             empties: 0,
+            step_count: 1,
+            ascii,
+            wind,
+            mix,
+            no_reset,
+            loop_seed: false,
+            quiet: false,
+            tick_rate: 0,
+            seed,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        let tick_rate = Duration::from_millis(8);
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        self.loop_seed = options.loop_seed;
+        let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
         self.reset();
-        let mut i: u32 = 1;
 
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| {
+                self.draw(frame, options.glyphs())
+            })?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
                     Event::Resize(_columns, _rows) => {
                         // self.debug_text = format!("{} {}", columns, rows);
                     }
                     _ => (),
                 }
             }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
 
             if last_tick.elapsed() >= tick_rate {
                 last_tick = Instant::now();
+                // speed only changes how many steps run per frame, every step is the same
+                // so a seed gives the same result at any speed
                 for _ in 0..self.speed {
-                    self.on_tick();
-                    if !self.is_spawning {
-                        break;
-                    }
-
-                    if i.is_multiple_of(2) {
-                        let width = self.grid[0].len() as u64;
-                        let mut found = false;
-                        'reset_spawn: for _ in 0..3 {
-                            if self.grid[0][self.spawn_point].is_none() {
-                                found = true;
-                                break 'reset_spawn;
-                            } else {
-                                self.spawn_point = self.rng.rand_range(0..width) as usize;
-                                self.color = self.random_color();
-                            }
-                        }
-                        if !found {
-                            self.start_emptying();
-                        }
-
-                        self.grid[0][self.spawn_point] = Some(self.color);
-
-                        if self.rng.rand_range(0..self.particles) == 0 {
-                            self.spawn_point = self.rng.rand_range(0..width) as usize;
-                            self.color = self.random_color();
-                        }
-                    }
-                    if let Some(n) = self.flip_after {
-                        if i.is_multiple_of(n) {
-                            self.flip();
-                        }
-                    }
-                    i = i.wrapping_add(1);
+                    self.step();
                 }
+                if self.show_stats {
+                    self.update_stats();
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "sand --marker {} --tick-rate {} --seed {} --speed {} --obstacles {} --obstacle-len {} --obstacle-color {} --particles {} --spawn-rate {} --reset {} --emitters {} --emitter-mode {} --wind {}",
+            self.marker,
+            self.tick_rate,
+            self.seed,
+            self.speed,
+            self.obstacles,
+            self.obstacle_len,
+            self.obstacle_color,
+            self.particles,
+            self.spawn_rate,
+            self.empties_until_reset,
+            self.n_emitters,
+            value_enum_name(self.emitter_mode),
+            self.wind
+        );
+        if let Some(obstacle_seed) = self.obstacle_seed {
+            args += &format!(" --obstacle-seed {obstacle_seed}");
+        }
+        if let Some(terrain_density) = self.terrain {
+            args += &format!(" --terrain --terrain-density {terrain_density}");
+        }
+        if self.burying {
+            args += " --burying";
+        }
+        if let Some(flip_after) = self.flip_after {
+            args += &format!(" --flip-after {flip_after}");
+        }
+        if self.ascii {
+            args += " --ascii";
+        }
+        if self.mix {
+            args += " --mix";
+        }
+        if self.no_reset {
+            args += " --no-reset";
+        }
+        if self.show_stats {
+            args += " --stats";
+        }
+        args
+    }
 
-                if self.is_emptying {
-                    self.clear_floor();
+    /// advance the simulation by one step
+    fn step(&mut self) {
+        self.on_tick();
+        match self.phase {
+            Phase::Spawning => {
+                self.spawn_budget += self.spawn_rate;
+                if self.spawn_budget >= 1.0 {
+                    self.spawn_budget -= 1.0;
+                    self.spawn();
                 }
-                if self.is_emptying {
-                    let hash = calculate_hash(&self.grid);
-                    if self.hash_history.len() == 2 {
-                        if self.hash_history[0] == self.hash_history[1] {
-                            self.is_emptying = false;
-                            self.is_spawning = true;
-                            if self.empties == self.empties_until_reset {
-                                self.reset();
-                                self.empties = 0;
-                            }
-                            self.hash_history[0] = 1;
-                            self.hash_history[1] = 2;
-                        }
-                        self.hash_history.rotate_left(1);
-                        self.hash_history.pop();
+                if let Some(n) = self.flip_after {
+                    if self.step_count.is_multiple_of(n) {
+                        self.flip();
                     }
-                    self.hash_history.push(hash);
                 }
+                self.step_count = self.step_count.wrapping_add(1);
+            }
+            Phase::Emptying => {
+                let cleared = self.clear_floor();
+                // done once nothing falls or drops out anymore
+                if self.n_moved == 0 && !cleared {
+                    // at least one empty per reset, so --reset 0 acts like 1
+                    self.phase = if self.empties >= self.empties_until_reset {
+                        Phase::Resetting
+                    } else {
+                        Phase::Spawning
+                    };
+                }
+            }
+            Phase::Resetting => {
+                self.empties = 0;
+                self.auto_reset();
+            }
+            Phase::Full => {}
+        }
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> Option<CellState> {
+        self.grid.get(x, y).copied()
+    }
+
+    /// (width, height) of the board in cells
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.grid.dimensions()
+    }
+
+    /// number of sand particles, not counting obstacles
+    pub fn live_count(&self) -> usize {
+        self.grid
+            .cells()
+            .filter(|c| matches!(c, Some(color) if *color != OBSTACLE))
+            .count()
+    }
+
+    fn update_stats(&mut self) {
+        let (width, height) = self.dimensions();
+        let n_obstacles = self.grid.cells().filter(|c| **c == Some(OBSTACLE)).count();
+        let live = self.live_count();
+        let free_cells = (width * height).saturating_sub(n_obstacles).max(1);
+        self.debug_text = format!(
+            "falling {}\nsettled {}\nfilled {:.1}%",
+            self.n_moved,
+            live.saturating_sub(self.n_moved),
+            100.0 * live as f64 / free_cells as f64
+        );
+        // mixing makes new colors, so only the most common ones get a line
+        let mut counts: HashMap<u8, usize> = HashMap::new();
+        for color in self.grid.cells().flatten() {
+            if *color != OBSTACLE {
+                *counts.entry(*color).or_default() += 1;
             }
         }
-        Ok(())
+        let mut counts: Vec<(u8, usize)> = counts.into_iter().collect();
+        counts.sort_by_key(|&(color, count)| (std::cmp::Reverse(count), color));
+        for (color, count) in counts.iter().take(STATS_COLORS) {
+            self.debug_text += &format!("\ncolor {color}: {count}");
+        }
+        if counts.len() > STATS_COLORS {
+            self.debug_text += &format!("\n{} more colors", counts.len() - STATS_COLORS);
+        }
     }
 
+    /// drain the pile, counting towards the next reset
     fn start_emptying(&mut self) {
-        self.is_emptying = true;
-        self.is_spawning = false;
+        self.phase = Phase::Emptying;
         self.empties += 1;
     }
 
-    fn clear_floor(&mut self) {
-        let floor = self.grid.iter_mut().last().unwrap();
-        for c in floor.iter_mut() {
-            *c = None;
+    /// remove everything on the bottom row, true if there was anything
+    fn clear_floor(&mut self) -> bool {
+        let floor = self.grid.height() - 1;
+        let mut cleared = false;
+        for c in self.grid.row_mut(floor) {
+            cleared |= c.take().is_some();
         }
+        cleared
     }
 
-    fn reset(&mut self) {
-        for line in self.grid.iter_mut() {
-            for val in line.iter_mut() {
-                *val = None;
+    /// drop one particle from every emitter, start emptying once one of them is buried
+    fn spawn(&mut self) {
+        let width = self.grid.width();
+        for k in 0..self.emitters.len() {
+            if self.emitter_mode == EmitterMode::Sweep {
+                let emitter = &mut self.emitters[k];
+                emitter.x += emitter.direction * SWEEP_SPEED;
+                if emitter.x <= 0.0 || emitter.x >= (width - 1) as f64 {
+                    emitter.x = emitter.x.clamp(0.0, (width - 1) as f64);
+                    emitter.direction = -emitter.direction;
+                }
             }
-        }
-        let board_width = self.grid[0].len() as u64;
-        self.spawn_point = self.rng.rand_range(0..board_width) as usize;
-        self.color = self.random_color();
 
-        let bounds_x = (0, board_width);
-        let board_height = self.grid.len();
-        let bounds_y = (
-            (board_height as f64 * 0.1) as u64,
-            (board_height as f64 * 0.90) as u64,
-        );
-        for _ in 0..self.obstacles {
-            let r = self.rng.rand_range(50..100) as f32 * 0.01;
-            let mut obstacle_len = (board_width as f64 * 0.15) as i32;
-            obstacle_len = (obstacle_len.min(self.obstacle_len as i32) as f32 * r) as i32;
-
-            let x0 = self.rng.rand_range(bounds_x.0..bounds_x.1) as i32;
-            let y0 = self.rng.rand_range(bounds_y.0..bounds_y.1) as i32;
-            let sign = match self.rng.rand_range(0..2) {
-                0 => -1,
-                1 => 1,
-                _ => unreachable!(),
-            };
-            let y_mult = match self.rng.rand_range(0..3) {
-                0 => 1.0,
-                1 => 0.7,
-                2 => 0.0,
-                _ => unreachable!(),
-            };
-            for i in 0..obstacle_len {
-                let x = (x0 + sign * i) as usize;
-                let y = (y0 + (y_mult * i as f64) as i32) as usize;
-                if x >= board_width as usize || y >= board_height {
-                    continue;
+            let mut found = false;
+            'reset_spawn: for _ in 0..3 {
+                if self.grid[(self.emitters[k].x as usize, 0)].is_none() {
+                    found = true;
+                    break 'reset_spawn;
+                } else if self.emitter_mode == EmitterMode::Sweep {
+                    break 'reset_spawn;
+                } else {
+                    self.relocate_emitter(k);
                 }
-                if let Some(1) = self.grid[y][x] {
-                    break;
+            }
+            if !found {
+                if self.no_reset {
+                    self.phase = Phase::Full;
+                } else {
+                    self.start_emptying();
+                }
+                return;
+            }
+
+            let emitter = &self.emitters[k];
+            self.grid[(emitter.x as usize, 0)] = Some(emitter.color);
+
+            match self.emitter_mode {
+                EmitterMode::FullWidth => {
+                    self.emitters[k].x = self.rng.rand_range(0..width as u64) as f64;
+                }
+                _ => {
+                    if self.rng.rand_range(0..self.particles) == 0 {
+                        self.relocate_emitter(k);
+                    }
                 }
-                self.grid[y][x] = Some(1);
             }
         }
     }
 
+    /// move emitter `k` to a random column with a new color, sweeping emitters only change color
+    fn relocate_emitter(&mut self, k: usize) {
+        if self.emitter_mode != EmitterMode::Sweep {
+            let width = self.grid.width() as u64;
+            self.emitters[k].x = self.rng.rand_range(0..width) as f64;
+        }
+        self.emitters[k].color = self.random_color(k);
+    }
+
+    /// reset on its own, starting from a new seed with --loop-seed
+    fn auto_reset(&mut self) {
+        if self.loop_seed {
+            self.seed = next_seed(&mut self.rng);
+            self.rng = oorandom::Rand64::new(self.seed);
+        }
+        self.reset();
+    }
+
+    /// start over from `seed`, n and N step through seeds
+    fn set_seed(&mut self, seed: u128) {
+        self.seed = seed;
+        self.rng = oorandom::Rand64::new(seed);
+        self.reset();
+        self.debug_text = format!("seed: {seed}");
+    }
+
+    fn reset(&mut self) {
+        self.grid.fill(None);
+        // a pile that is draining keeps draining the new board
+        if self.phase != Phase::Emptying {
+            self.phase = Phase::Spawning;
+        }
+        let board_width = self.grid.width() as u64;
+        self.emitters.clear();
+        for k in 0..self.n_emitters {
+            let x = if self.emitter_mode == EmitterMode::Sweep {
+                // spread out evenly
+                board_width as f64 * (k as f64 + 0.5) / self.n_emitters as f64
+            } else {
+                self.rng.rand_range(0..board_width) as f64
+            };
+            let direction = if k % 2 == 0 { 1.0 } else { -1.0 };
+            self.emitters.push(Emitter {
+                x: x.min((board_width - 1) as f64),
+                direction,
+                color: 0,
+            });
+            self.emitters[k].color = self.random_color(k);
+        }
+        let mut obstacle_rng = self.obstacle_seed.map(oorandom::Rand64::new);
+        let rng = obstacle_rng.as_mut().unwrap_or(&mut self.rng);
+        match self.terrain {
+            Some(density) => generate_terrain(&mut self.grid, rng, 1.0 - density as f64),
+            None => particle_grid::place_obstacles(
+                &mut self.grid,
+                rng,
+                self.obstacles,
+                self.obstacle_len,
+            ),
+        }
+    }
+
     fn flip(&mut self) {
-        self.grid = self.grid.clone().into_iter().rev().collect();
+        self.grid.flip_vertical();
+    }
+
+    /// random color for emitter `k`, avoiding the colors of the other emitters while possible
+    fn random_color(&mut self, k: usize) -> u8 {
+        let free: Vec<u8> = (2..8)
+            .filter(|c| {
+                !self
+                    .emitters
+                    .iter()
+                    .enumerate()
+                    .any(|(j, e)| j != k && e.color == *c)
+            })
+            .collect();
+        if free.is_empty() {
+            self.rng.rand_range(2..8) as u8
+        } else {
+            free[self.rng.rand_range(0..free.len() as u64) as usize]
+        }
     }
 
-    fn random_color(&mut self) -> u8 {
-        self.rng.rand_range(2..8) as u8
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.exit = true,
+            Action::Reset => self.reset(),
+            Action::NextSeed => self.set_seed(self.seed.wrapping_add(1)),
+            Action::PrevSeed => self.set_seed(self.seed.wrapping_sub(1)),
+            Action::PanLeft => self.adjust_wind(-0.1),
+            Action::PanRight => self.adjust_wind(0.1),
+            _ => (),
+        }
     }
 
+    /// keys only sand has, everything else goes through the shared bindings
     fn handle_key_press(&mut self, key: event::KeyEvent) {
+        if let Some(action) = input::map_key(key) {
+            self.apply(action);
+            return;
+        }
         if key.kind != KeyEventKind::Press {
             return;
         }
         match key.code {
-            KeyCode::Char('r') => self.reset(),
             KeyCode::Char('v') => self.flip(),
-            KeyCode::Char('e') => {
-                self.is_emptying = !self.is_emptying;
-                self.is_spawning = !self.is_spawning;
-            }
-            _ => {
-                if is_quit_key(key) {
-                    self.exit = true;
+            KeyCode::Char(',') => self.apply(Action::PanLeft),
+            KeyCode::Char('.') => self.apply(Action::PanRight),
+            KeyCode::Char('i') => {
+                self.show_stats = !self.show_stats;
+                if self.show_stats {
+                    self.update_stats();
+                } else {
+                    self.debug_text.clear();
                 }
             }
+            // by hand, so it doesn't count towards --reset
+            KeyCode::Char('e') => {
+                self.phase = match self.phase {
+                    Phase::Emptying => Phase::Spawning,
+                    _ => Phase::Emptying,
+                };
+            }
+            _ => (),
+        }
+    }
+
+    /// column a grain falling from (x, y) lands in, wind blows some of them sideways
+    fn drift_target(&mut self, x: usize, y: usize, width: usize) -> usize {
+        if self.wind == 0.0 || self.rng.rand_float() >= 0.5 * self.wind.abs() {
+            return x;
+        }
+        let side = if self.wind > 0.0 {
+            x + 1
+        } else {
+            x.wrapping_sub(1)
+        };
+        if side < width && self.grid[(side, y)].is_none() && self.grid[(side, y + 1)].is_none() {
+            side
+        } else {
+            x
         }
     }
 
+    fn adjust_wind(&mut self, delta: f64) {
+        self.wind = (self.wind + delta).clamp(-1.0, 1.0);
+        self.debug_text = format!("wind: {:.1}", self.wind);
+    }
+
     fn on_tick(&mut self) {
+        self.n_moved = 0;
         if self.grid.is_empty() {
             return;
         }
-        let height = self.grid.len();
-        let width = self.grid[0].len();
+        let (width, height) = self.dimensions();
         #[allow(clippy::needless_range_loop)]
         for y in (0..(height - 1)).rev() {
             for x in 0..width {
-                if self.grid[y][x].is_some() {
-                    if self.grid[y][x].unwrap() == 1 {
+                if self.grid[(x, y)].is_some() {
+                    if self.grid[(x, y)].unwrap() == OBSTACLE {
                         continue;
                     }
 
-                    if self.grid[y + 1][x].is_none() {
-                        self.grid[y + 1][x] = self.grid[y][x];
-                        self.grid[y][x] = None;
+                    let target_x = if self.grid[(x, y + 1)].is_none() {
+                        Some(self.drift_target(x, y, width))
                     } else if x > 0
                         && x < (width - 1)
-                        && self.grid[y + 1][x - 1].is_none()
-                        && self.grid[y + 1][x + 1].is_none()
-                        && self.grid[y][x - 1].is_none()
-                        && self.grid[y][x + 1].is_none()
+                        && self.grid[(x - 1, y + 1)].is_none()
+                        && self.grid[(x + 1, y + 1)].is_none()
+                        && self.grid[(x - 1, y)].is_none()
+                        && self.grid[(x + 1, y)].is_none()
                     {
-                        match self.rng.rand_range(0..2) {
-                            0 => self.grid[y + 1][x - 1] = self.grid[y][x],
-                            1 => self.grid[y + 1][x + 1] = self.grid[y][x],
-                            _ => unreachable!(),
+                        let right = if self.wind == 0.0 {
+                            self.rng.rand_range(0..2) == 1
+                        } else {
+                            // keep some chance for both sides so piles still spread
+                            self.rng.rand_float() < (0.5 + 0.5 * self.wind).clamp(0.05, 0.95)
+                        };
+                        if right {
+                            Some(x + 1)
+                        } else {
+                            Some(x - 1)
                         }
-                        self.grid[y][x] = None;
                     } else if x > 0
-                        && self.grid[y + 1][x - 1].is_none()
-                        && self.grid[y][x - 1].is_none()
+                        && self.grid[(x - 1, y + 1)].is_none()
+                        && self.grid[(x - 1, y)].is_none()
                     {
-                        self.grid[y + 1][x - 1] = self.grid[y][x];
-                        self.grid[y][x] = None;
+                        Some(x - 1)
                     } else if x < (width - 1)
-                        && self.grid[y + 1][x + 1].is_none()
-                        && self.grid[y][x + 1].is_none()
+                        && self.grid[(x + 1, y + 1)].is_none()
+                        && self.grid[(x + 1, y)].is_none()
                     {
-                        self.grid[y + 1][x + 1] = self.grid[y][x];
-                        self.grid[y][x] = None;
+                        Some(x + 1)
+                    } else if self.burying {
+                        self.over_obstacle(x, y)
+                    } else {
+                        None
+                    };
+
+                    if let Some(target_x) = target_x {
+                        self.n_moved += 1;
+                        self.grid[(target_x, y + 1)] = self.grid[(x, y)].take();
+                        if self.mix && self.is_resting(target_x, y + 1) {
+                            self.mix_color(target_x, y + 1);
+                        }
                     }
                 }
             }
         }
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+    /// column a grain at (x, y) lands in by sliding over the top of an obstacle
+    /// diagonally below it, None if neither side is free
+    fn over_obstacle(&mut self, x: usize, y: usize) -> Option<usize> {
+        let width = self.grid.width();
+        let passable = |side: usize, beyond: usize| {
+            self.grid[(side, y + 1)] == Some(OBSTACLE)
+                && self.grid[(side, y)].is_none()
+                && self.grid[(beyond, y)].is_none()
+                && self.grid[(beyond, y + 1)].is_none()
+        };
+        let left = x >= 2 && passable(x - 1, x - 2);
+        let right = x + 2 < width && passable(x + 1, x + 2);
+        match (left, right) {
+            (true, true) if self.rng.rand_range(0..2) == 1 => Some(x + 2),
+            (true, _) => Some(x - 2),
+            (false, true) => Some(x + 2),
+            (false, false) => None,
+        }
+    }
+
+    /// whether the grain at (x, y) has nowhere left to fall
+    fn is_resting(&self, x: usize, y: usize) -> bool {
+        let width = self.grid.width();
+        let blocked = |x: usize, y: usize| self.grid[(x, y)].is_some();
+        y + 1 == self.grid.height()
+            || (blocked(x, y + 1)
+                && (x == 0 || blocked(x - 1, y + 1) || blocked(x - 1, y))
+                && (x == width - 1 || blocked(x + 1, y + 1) || blocked(x + 1, y)))
+    }
+
+    /// blend the color of the grain at (x, y) halfway towards its differently colored neighbors
+    fn mix_color(&mut self, x: usize, y: usize) {
+        let Some(color) = self.grid[(x, y)] else {
+            return;
+        };
+        let width = self.grid.width();
+        let mut neighbors = vec![(x, y + 1)];
+        if x > 0 {
+            neighbors.push((x - 1, y));
+        }
+        if x < width - 1 {
+            neighbors.push((x + 1, y));
+        }
+        let others: Vec<(u8, u8, u8)> = neighbors
+            .into_iter()
+            .filter(|&(_, ny)| ny < self.grid.height())
+            .filter_map(|(nx, ny)| self.grid[(nx, ny)])
+            .filter(|&c| c != OBSTACLE && c != color)
+            .map(indexed_to_rgb)
+            .collect();
+        if others.is_empty() {
+            return;
+        }
+        let n = others.len() as u32;
+        let average = |channel: fn(&(u8, u8, u8)) -> u8| {
+            others.iter().map(|c| channel(c) as u32).sum::<u32>() / n
+        };
+        let own = indexed_to_rgb(color);
+        let blend = |own: u8, other: u32| ((own as u32 + other) / 2) as u8;
+        // the color cube never hands out OBSTACLE
+        self.grid[(x, y)] = Some(rgb_to_indexed(
+            blend(own.0, average(|c| c.0)),
+            blend(own.1, average(|c| c.1)),
+            blend(own.2, average(|c| c.2)),
+        ));
+    }
+
+    fn draw(&self, frame: &mut Frame, glyphs: &GlyphSet) {
+        if self.ascii {
+            let area = frame.area();
+            let (width, height) = self.dimensions();
+            // row 0 of the grid is the top
+            draw_ascii(
+                frame.buffer_mut(),
+                area,
+                glyphs,
+                width,
+                height,
+                |x, y| match self.grid[(x, height - 1 - y)] {
+                    Some(OBSTACLE) => 1.0,
+                    Some(color) => color as f64 / 8.0,
+                    None => 0.0,
+                },
+            );
+        } else {
+            frame.render_widget(self.canvas(), frame.area());
+        }
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
@@ -317,26 +778,120 @@ impl App {
         Canvas::default()
             .marker(self.marker)
             .paint(|ctx| {
-                if self.grid.is_empty() {
-                    return;
-                }
-                let width = self.grid[0].len();
-                let height = self.grid.len();
-
-                for (y, line) in self.grid.iter().rev().enumerate() {
-                    let y = map_range(y as f64, 0.0, height as f64, 0.0, self.playground.y);
-                    for (x, val) in line.iter().enumerate() {
-                        let x = map_range(x as f64, 0.0, width as f64, 0.0, self.playground.x);
-                        if let &Some(color) = val {
-                            ctx.draw(&Points {
-                                coords: &[(x, y)],
-                                color: Color::Indexed(color),
-                            });
+                particle_grid::paint(
+                    ctx,
+                    &self.grid,
+                    self.playground.x,
+                    self.playground.y,
+                    |_, _, color| {
+                        if color == OBSTACLE {
+                            Color::Indexed(self.obstacle_color)
+                        } else {
+                            Color::Indexed(color)
                         }
-                    }
-                }
+                    },
+                );
             })
             .x_bounds([0.0, self.playground.x])
             .y_bounds([0.0, self.playground.y])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot;
+
+    fn app(obstacles: usize, empties_until_reset: usize) -> App {
+        sized_app(60, 20, obstacles, empties_until_reset)
+    }
+
+    fn sized_app(width: u16, height: u16, obstacles: usize, empties_until_reset: usize) -> App {
+        App::new(
+            width,
+            height,
+            Marker::HalfBlock,
+            5,
+            None,
+            1,
+            obstacles,
+            5,
+            8,
+            None,
+            false,
+            100,
+            0.5,
+            None,
+            empties_until_reset,
+            1,
+            EmitterMode::Point,
+            false,
+            0.0,
+            false,
+            false,
+            false,
+        )
+    }
+
+    fn board(app: &App) -> String {
+        let (width, height) = app.dimensions();
+        let mut board = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                board.push(snapshot::cell_char(app.cell(x, y).unwrap(), Some(OBSTACLE)));
+            }
+            board.push('\n');
+        }
+        board
+    }
+
+    #[test]
+    fn pile_matches_snapshot() {
+        let mut app = app(40, 3);
+        app.reset();
+        for _ in 0..1500 {
+            app.step();
+        }
+        snapshot::assert_snapshot("sand", &board(&app));
+    }
+
+    #[test]
+    fn obstacles_capped_for_board() {
+        let mut app = app(usize::MAX, 3);
+        app.reset();
+        let max_obstacles = particle_grid::max_obstacles(&app.grid);
+        assert_eq!(app.obstacles, max_obstacles);
+        // each obstacle is a line of at most --obstacle-len cells
+        let n_cells = app.grid.cells().filter(|c| **c == Some(OBSTACLE)).count();
+        assert!(n_cells > 0);
+        assert!(n_cells <= max_obstacles * app.obstacle_len);
+    }
+
+    #[test]
+    fn resets_after_every_nth_empty() {
+        for empties_until_reset in [1, 3] {
+            // small so the pile fills up quickly
+            let mut app = sized_app(16, 8, 0, empties_until_reset);
+            app.reset();
+            let mut empties = 0;
+            let mut resets = 0;
+            for _ in 0..100_000 {
+                let before = app.phase;
+                app.step();
+                match (before, app.phase) {
+                    (Phase::Spawning, Phase::Emptying) => empties += 1,
+                    (Phase::Emptying, Phase::Resetting) => {
+                        assert_eq!(empties, empties_until_reset);
+                        empties = 0;
+                        resets += 1;
+                    }
+                    _ => (),
+                }
+                if resets == 3 {
+                    break;
+                }
+            }
+            assert_eq!(resets, 3);
+        }
+    }
+}
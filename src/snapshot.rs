@@ -0,0 +1,66 @@
+//! Snapshots for the tests, kept as text files in tests/snapshots.
+//!
+//! Run the tests with `UPDATE_SNAPSHOTS=1` to write new ones after a change that is meant
+//! to alter what a toy draws.
+
+use ratatui::{buffer::Buffer, style::Color};
+use std::path::PathBuf;
+
+/// compare `actual` to the committed snapshot `name`
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "could not read {}: {e}, run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        )
+    });
+    assert!(
+        actual == expected,
+        "{name} differs from its snapshot, run with UPDATE_SNAPSHOTS=1 if that was intended\n\
+         expected:\n{expected}\nactual:\n{actual}"
+    );
+}
+
+/// one character per cell: '.' for empty, '#' for obstacles, otherwise the color in base 36
+pub fn cell_char(color: Option<u8>, obstacle: Option<u8>) -> char {
+    match color {
+        None => '.',
+        Some(c) if Some(c) == obstacle => '#',
+        Some(c) => char::from_digit(c as u32, 36).unwrap_or('*'),
+    }
+}
+
+/// a drawn buffer as its symbols followed by the foreground and background colors of every
+/// cell: '.' for the default color, indexed colors in base 36 and '*' for anything else
+pub fn buffer_text(buffer: &Buffer) -> String {
+    let color_char = |color| match color {
+        Color::Reset => '.',
+        Color::Indexed(c) => char::from_digit(c as u32, 36).unwrap_or('*'),
+        _ => '*',
+    };
+    let area = buffer.area;
+    let mut text = String::new();
+    for part in 0..3 {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = &buffer[(x, y)];
+                match part {
+                    0 => text += cell.symbol(),
+                    1 => text.push(color_char(cell.fg)),
+                    _ => text.push(color_char(cell.bg)),
+                }
+            }
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text
+}
@@ -1,6 +1,7 @@
-use crate::utils::is_quit_key;
+use crate::gamepad;
+use crate::input::{self, Action};
+use crate::utils::{draw_palette_swatch, draw_thick_line, next_seed, Backoff, RunOptions};
 use color_eyre::Result;
-use crossterm::event::KeyEventKind;
 use glam::DVec2;
 use oorandom::Rand64;
 use ratatui::{
@@ -8,17 +9,29 @@ use ratatui::{
     layout::Rect,
     style::Color,
     symbols::Marker,
-    widgets::{
-        canvas::{Canvas, Line},
-        Paragraph, Widget,
-    },
+    widgets::{canvas::Canvas, Paragraph, Widget},
     DefaultTerminal, Frame,
 };
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+// walkers recycled per tick in continuous mode
+const RECYCLE_PER_TICK: usize = 2;
+/// points kept per walker without --max-history, more than a walker crossing the screen needs
+pub const DEFAULT_MAX_HISTORY: usize = 5000;
+
+/// color of a walker splitting off one with `color_index`, walks through 1..=n_colors
+/// without hitting 0 (black)
+fn next_color(color_index: u8, n_colors: u8) -> u8 {
+    1 + (color_index % n_colors)
+}
+
 #[derive(Clone)]
 struct Walker {
-    history: Vec<DVec2>,
+    // the trail to draw, oldest points drop off past --max-history
+    history: VecDeque<DVec2>,
+    // steps since starting or splitting off, paces the splits
+    steps: usize,
     location: DVec2,
     direction: DVec2,
     active: bool,
@@ -26,26 +39,59 @@ struct Walker {
     color_index: u8,
 }
 
+impl Walker {
+    /// reflect off the edges of `bounds`, staying inside
+    fn bounce(&mut self, bounds: Rect) {
+        let (left, right) = (bounds.left() as f64, bounds.right() as f64);
+        let (top, bottom) = (bounds.top() as f64, bounds.bottom() as f64);
+        if !(left..=right).contains(&self.location.x) {
+            self.direction.x = -self.direction.x;
+            self.location.x = self.location.x.clamp(left, right);
+        }
+        if !(top..=bottom).contains(&self.location.y) {
+            self.direction.y = -self.direction.y;
+            self.location.y = self.location.y.clamp(top, bottom);
+        }
+    }
+}
+
 pub struct App {
     exit: bool,
     walkers: Vec<Walker>,
     playground: Rect,
     ticks_since_stopped: u64,
     debug_text: String,
+    quiet: bool,
+    // c shows the colors in use
+    show_palette: bool,
     marker: Marker,
     rng: Rand64,
     max_walkers: usize,
     rotate: bool,
+    n_colors: u8,
+    continuous: bool,
+    bounce: bool,
+    thickness: u8,
+    max_history: usize,
+    loop_seed: bool,
+    tick_rate: u64,
+    seed: u128,
 }
 
 impl App {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         terminal_width: u16,
         terminal_height: u16,
         marker: Marker,
         rotate: bool,
         max_walkers: u16,
+        n_colors: u8,
+        continuous: bool,
+        bounce: bool,
         seed: u128,
+        thickness: u8,
+        max_history: usize,
     ) -> Self {
         let scale_factor = terminal_height as f32 / terminal_width as f32;
         let font_scale_factor = 2.0;
@@ -62,30 +108,59 @@ impl App {
             rng,
             max_walkers: max_walkers as usize,
             rotate,
+            n_colors,
+            continuous,
+            bounce,
+            loop_seed: false,
+            thickness,
+            max_history,
+            quiet: false,
+            show_palette: false,
+            tick_rate: 0,
+            seed,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        let tick_rate = Duration::from_millis(16);
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        self.loop_seed = options.loop_seed;
+        let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
         self.reset();
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
                     Event::Resize(_columns, _rows) => {
                         // self.debug_text = format!("{} {}", columns, rows);
                     }
                     _ => (),
                 }
             }
+            for action in gamepad::poll_actions() {
+                backoff.reset();
+                self.apply(action);
+            }
 
             if last_tick.elapsed() >= tick_rate {
-                if self.ticks_since_stopped > 200 {
+                if self.continuous {
+                    self.recycle_walkers();
+                } else if self.ticks_since_stopped > 200 {
                     self.ticks_since_stopped = 0;
-                    self.reset();
+                    self.auto_reset();
                 }
                 self.on_tick();
                 last_tick = Instant::now();
@@ -97,7 +172,14 @@ impl App {
                     }
                     walker.location.x += walker.direction.x;
                     walker.location.y += walker.direction.y;
-                    walker.history.push(walker.location);
+                    if self.bounce {
+                        walker.bounce(self.playground);
+                    }
+                    walker.history.push_back(walker.location);
+                    walker.steps += 1;
+                    if walker.history.len() > self.max_history {
+                        walker.history.pop_front();
+                    }
                     if !(self.playground.left()..=self.playground.right())
                         .contains(&(walker.location.x as u16))
                         || !(self.playground.top()..=self.playground.bottom())
@@ -106,8 +188,7 @@ impl App {
                         walker.active = false;
                         continue;
                     }
-                    if walker.history.len() % walker.split_len == 0 && n_walkers < self.max_walkers
-                    {
+                    if walker.steps % walker.split_len == 0 && n_walkers < self.max_walkers {
                         let dir = walker.direction;
                         walker.direction *= self.rng.rand_float() + 0.5;
                         to_split.push(walker.clone());
@@ -117,53 +198,139 @@ impl App {
 
                 for mut split_walker in to_split.into_iter() {
                     split_walker.history.clear();
-                    split_walker.history.push(split_walker.location);
+                    split_walker.history.push_back(split_walker.location);
+                    split_walker.steps = 1;
                     let dir = split_walker.direction;
                     split_walker.direction = DVec2::new(-dir.y, dir.x);
                     split_walker.split_len = self.rng.rand_range(20..70) as usize;
-                    split_walker.color_index = (split_walker.color_index + 1) % 12;
-                    if split_walker.color_index == 0 {
-                        split_walker.color_index += 1;
-                    }
+                    split_walker.color_index = next_color(split_walker.color_index, self.n_colors);
                     self.walkers.push(split_walker);
                 }
             }
         }
-        Ok(())
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "splits --marker {} --tick-rate {} --max-walkers {} --n-colors {} --seed {}",
+            self.marker, self.tick_rate, self.max_walkers, self.n_colors, self.seed
+        );
+        if self.rotate {
+            args += " --rotate";
+        }
+        if self.continuous {
+            args += " --continuous";
+        }
+        if self.bounce {
+            args += " --bounce";
+        }
+        if self.thickness > 1 {
+            args += &format!(" --thickness {}", self.thickness);
+        }
+        if self.max_history != DEFAULT_MAX_HISTORY {
+            args += &format!(" --max-history {}", self.max_history);
+        }
+        args
+    }
+
+    /// reset on its own, starting from a new seed with --loop-seed
+    fn auto_reset(&mut self) {
+        if self.loop_seed {
+            self.seed = next_seed(&mut self.rng);
+            self.rng = oorandom::Rand64::new(self.seed);
+        }
+        self.reset();
+    }
+
+    /// start over from `seed`, n and N step through seeds
+    fn set_seed(&mut self, seed: u128) {
+        self.seed = seed;
+        self.rng = oorandom::Rand64::new(seed);
+        self.reset();
+        self.debug_text = format!("seed: {seed}");
     }
 
     fn reset(&mut self) {
         self.walkers.clear();
-        let middle_x = self.playground.right() as f64 * 0.5;
-        let middle_y = self.playground.bottom() as f64 * 0.5;
         let direction = if self.rotate {
-            let v = DVec2::new(self.rng.rand_float(), self.rng.rand_float());
-            if v.x + v.y > 0.2 {
-                v
-            } else {
-                DVec2::new(self.rng.rand_float() + 0.1, self.rng.rand_float() + 0.1)
-            }
+            self.random_direction()
         } else {
             DVec2::new(0.0, 0.7)
         };
+        let first_walker = self.new_walker(direction);
+        self.walkers.push(first_walker);
+    }
+
+    fn random_direction(&mut self) -> DVec2 {
+        let v = DVec2::new(self.rng.rand_float(), self.rng.rand_float());
+        if v.x + v.y > 0.2 {
+            v
+        } else {
+            DVec2::new(self.rng.rand_float() + 0.1, self.rng.rand_float() + 0.1)
+        }
+    }
 
-        let first_walker = Walker {
-            history: Vec::new(),
+    /// walker starting from the middle of the playground
+    fn new_walker(&mut self, direction: DVec2) -> Walker {
+        let middle_x = self.playground.right() as f64 * 0.5;
+        let middle_y = self.playground.bottom() as f64 * 0.5;
+        Walker {
+            history: VecDeque::new(),
+            steps: 0,
             location: DVec2::new(middle_x, middle_y),
             direction,
             active: true,
             split_len: 5,
-            color_index: self.rng.rand_range(1..11) as u8,
-        };
-        self.walkers.push(first_walker);
+            color_index: self.rng.rand_range(1..self.n_colors as u64 + 1) as u8,
+        }
     }
 
-    fn handle_key_press(&mut self, key: event::KeyEvent) {
-        if key.kind != KeyEventKind::Press {
-            return;
+    /// drop a few walkers that left the playground and send a new one from the middle
+    fn recycle_walkers(&mut self) {
+        let mut n_recycled = 0;
+        let mut i = 0;
+        while i < self.walkers.len() && n_recycled < RECYCLE_PER_TICK {
+            if self.walkers[i].active {
+                i += 1;
+            } else {
+                self.walkers.swap_remove(i);
+                n_recycled += 1;
+            }
         }
-        if is_quit_key(key) {
-            self.exit = true;
+        if n_recycled > 0 && self.walkers.len() < self.max_walkers {
+            let direction = if self.rotate {
+                self.random_direction()
+            } else {
+                // a random axis so recycled walkers don't all retrace the first one
+                let axis = DVec2::new(0.0, 0.7);
+                match self.rng.rand_range(0..4) {
+                    0 => axis,
+                    1 => axis.perp(),
+                    2 => -axis,
+                    _ => -axis.perp(),
+                }
+            };
+            let walker = self.new_walker(direction);
+            self.walkers.push(walker);
+        }
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.exit = true,
+            Action::Reset => self.reset(),
+            Action::NextSeed => self.set_seed(self.seed.wrapping_add(1)),
+            Action::PrevSeed => self.set_seed(self.seed.wrapping_sub(1)),
+            Action::TogglePalette => self.show_palette = !self.show_palette,
+            _ => (),
+        }
+    }
+
+    fn handle_key_press(&mut self, key: event::KeyEvent) {
+        if let Some(action) = input::map_key(key) {
+            self.apply(action);
         }
     }
 
@@ -175,7 +342,11 @@ impl App {
 
     fn draw(&self, frame: &mut Frame) {
         frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+        if self.show_palette && !self.quiet {
+            let area = frame.area();
+            draw_palette_swatch(frame.buffer_mut(), area, 1..=self.n_colors);
+        }
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
@@ -186,13 +357,10 @@ impl App {
             .marker(self.marker)
             .paint(|ctx| {
                 for walker in self.walkers.iter() {
-                    for w in walker.history.windows(2) {
-                        let line_points = w;
-                        let p0 = line_points[0];
-                        let p1 = line_points[1];
-                        let line =
-                            Line::new(p0.x, p0.y, p1.x, p1.y, Color::Indexed(walker.color_index));
-                        ctx.draw(&line);
+                    let history = &walker.history;
+                    for (&p0, &p1) in history.iter().zip(history.iter().skip(1)) {
+                        let color = Color::Indexed(walker.color_index);
+                        draw_thick_line(ctx, p0, p1, color, self.thickness);
                     }
                 }
             })
@@ -206,3 +374,25 @@ impl App {
             ])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn splits_get_distinct_colors() {
+        for n_colors in 1..=20 {
+            for splits in 1..=n_colors {
+                for first in 1..=n_colors {
+                    let colors: HashSet<u8> =
+                        std::iter::successors(Some(first), |&c| Some(next_color(c, n_colors)))
+                            .take(splits as usize)
+                            .collect();
+                    assert_eq!(colors.len(), splits as usize);
+                    assert!(colors.iter().all(|c| (1..=n_colors).contains(c)));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::utils::{depth_color, is_quit_key, Backoff, RunOptions, ToScreenPos};
+use color_eyre::Result;
+use crossterm::event::KeyEventKind;
+use glam::DVec3;
+use oorandom::Rand64;
+use ratatui::{
+    crossterm::event::{self, Event},
+    layout::Rect,
+    symbols::Marker,
+    widgets::{
+        canvas::{Canvas, Line},
+        Paragraph, Widget,
+    },
+    DefaultTerminal, Frame,
+};
+use std::time::{Duration, Instant};
+
+const NEAR: f64 = 1.0;
+const FAR: f64 = 100.0;
+const SPREAD: f64 = 60.0;
+
+pub struct App {
+    exit: bool,
+    stars: Vec<DVec3>,
+    playground: Rect,
+    debug_text: String,
+    quiet: bool,
+    marker: Marker,
+    rng: Rand64,
+    speed: f64,
+    val: f64,
+    tick_rate: u64,
+    seed: u128,
+}
+
+impl App {
+    pub fn new(
+        terminal_width: u16,
+        terminal_height: u16,
+        marker: Marker,
+        count: u16,
+        speed: f64,
+        seed: u128,
+    ) -> Self {
+        let scale_factor = terminal_height as f32 / terminal_width as f32;
+        let font_scale_factor = 2.0;
+        let width = 200.0;
+        let height = width * scale_factor * font_scale_factor;
+        let mut app = Self {
+            exit: false,
+            stars: Vec::with_capacity(count as usize),
+            playground: Rect::new(0, 0, width as u16, height as u16),
+            debug_text: String::new(),
+            marker,
+            rng: oorandom::Rand64::new(seed),
+            speed,
+            val: 0.01,
+            quiet: false,
+            tick_rate: 0,
+            seed,
+        };
+        for _ in 0..count {
+            let z = NEAR + app.rng.rand_float() * (FAR - NEAR);
+            let star = app.new_star(z);
+            app.stars.push(star);
+        }
+        app
+    }
+
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        let tick_rate = Duration::from_millis(tick_rate);
+        let mut last_tick = Instant::now();
+        let mut backoff = Backoff::new(options.adaptive);
+
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| self.draw(frame))?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
+            if event::poll(timeout)? {
+                backoff.reset();
+                match event::read()? {
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
+                    Event::Resize(_columns, _rows) => {
+                        // self.debug_text = format!("{} {}", columns, rows);
+                    }
+                    _ => (),
+                }
+            }
+
+            if last_tick.elapsed() >= tick_rate {
+                self.on_tick();
+                last_tick = Instant::now();
+            }
+        }
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        format!(
+            "stars --marker {} --tick-rate {} --count {} --speed {} --seed {}",
+            self.marker,
+            self.tick_rate,
+            self.stars.len(),
+            self.speed,
+            self.seed
+        )
+    }
+
+    fn new_star(&mut self, z: f64) -> DVec3 {
+        DVec3::new(
+            (self.rng.rand_float() - 0.5) * 2.0 * SPREAD,
+            (self.rng.rand_float() - 0.5) * 2.0 * SPREAD,
+            z,
+        )
+    }
+
+    fn handle_key_press(&mut self, key: event::KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        if is_quit_key(key) {
+            self.exit = true;
+        }
+    }
+
+    fn on_tick(&mut self) {
+        for i in 0..self.stars.len() {
+            self.stars[i].z -= self.speed;
+            if self.stars[i].z < NEAR {
+                self.stars[i] = self.new_star(FAR);
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self.canvas(), frame.area());
+        if !self.quiet && !self.debug_text.is_empty() {
+            let debug_text = Paragraph::new(self.debug_text.clone());
+            frame.render_widget(debug_text, frame.area());
+        }
+    }
+
+    fn canvas(&self) -> impl Widget + '_ {
+        Canvas::default()
+            .marker(self.marker)
+            .paint(|ctx| {
+                for star in self.stars.iter() {
+                    // streak from where the star was a few ticks ago
+                    let tail = DVec3::new(star.x, star.y, (star.z + 3.0 * self.speed).min(FAR));
+                    let p0 = tail.to_screen_position(self.playground, self.val);
+                    let p1 = star.to_screen_position(self.playground, self.val);
+                    let color = depth_color(star.z, NEAR, FAR);
+                    ctx.draw(&Line::new(p0.x, p0.y, p1.x, p1.y, color));
+                }
+            })
+            .x_bounds([
+                self.playground.left() as f64,
+                self.playground.right() as f64,
+            ])
+            .y_bounds([
+                self.playground.top() as f64,
+                self.playground.bottom() as f64,
+            ])
+    }
+}
@@ -1,6 +1,11 @@
-use crate::utils::{calc_board_size_scaled, is_quit_key};
+use crate::glyphs::GlyphSet;
+use crate::utils::{
+    calc_board_size_scaled, draw_ascii, draw_palette_swatch, encode_ppm, indexed_to_rgb,
+    is_quit_key, sample_gradient, supports_truecolor, value_enum_name, Afterglow, AudioLevel,
+    AudioParam, Backoff, BeatFifo, Mirror, RunOptions, PPM_BLOCK_SIZE,
+};
 use color_eyre::Result;
-use crossterm::event::KeyEventKind;
+use crossterm::event::{KeyCode, KeyEventKind};
 use glam::DVec2;
 use ratatui::{
     crossterm::event::{self, Event},
@@ -13,18 +18,39 @@ use ratatui::{
     DefaultTerminal, Frame,
 };
 use std::f64::consts::PI;
+
+// seconds of animation a beat or an --at-tick step is worth
+const TICK_SECS: f64 = 0.008;
+// deepest of the --depth modes
+const MAX_DEPTH: u8 = 2;
 use std::time::{Duration, Instant};
 
 pub struct App {
-    grid: Vec<Vec<u8>>,
+    // palette position of every cell, only --smooth keeps the fraction between bands
+    grid: Vec<Vec<f64>>,
     exit: bool,
     playground: DVec2,
     debug_text: String,
+    quiet: bool,
+    // c shows the colors in use
+    show_palette: bool,
     marker: Marker,
     n_colors: u8,
+    // --audio-reactive colors scales n_colors by this
+    color_boost: f64,
     rotation_speed: f64,
+    // animation time, so the speed doesn't depend on the tick rate
+    elapsed_secs: f64,
     depth: u8,
     twist: bool,
+    mirror: Mirror,
+    ascii: bool,
+    persistence: f32,
+    afterglow: Option<Afterglow>,
+    smooth: bool,
+    // smooth only takes effect on terminals with 24 bit colors
+    truecolor: bool,
+    tick_rate: u64,
 }
 
 impl App {
@@ -37,6 +63,10 @@ impl App {
         rotation_speed: f64,
         depth: u8,
         twist: bool,
+        mirror: Mirror,
+        ascii: bool,
+        persistence: f32,
+        smooth: bool,
     ) -> Self {
         let mut grid = Vec::new();
 
@@ -44,7 +74,7 @@ impl App {
             calc_board_size_scaled(marker, terminal_width, terminal_height);
 
         for _ in 0..board_height {
-            grid.push(vec![0; board_width]);
+            grid.push(vec![0.0; board_width]);
         }
 
         Self {
@@ -54,23 +84,52 @@ impl App {
             marker,
             debug_text: String::new(),
             n_colors,
+            color_boost: 1.0,
             rotation_speed,
-            depth,
+            elapsed_secs: 0.0,
+            depth: depth.min(MAX_DEPTH),
             twist,
+            mirror,
+            ascii,
+            persistence,
+            afterglow: (persistence > 0.0)
+                .then(|| Afterglow::new(persistence as f64, board_width, board_height)),
+            smooth,
+            truecolor: supports_truecolor(),
+            quiet: false,
+            show_palette: false,
+            tick_rate: 0,
         }
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        let tick_rate = Duration::from_millis(8);
+    /// with `beat_fifo` the tunnel only moves on beats read from the pipe,
+    /// with `audio` the speed or number of colors follows the audio level
+    pub fn run(
+        mut self,
+        mut terminal: DefaultTerminal,
+        tick_rate: u64,
+        beat_fifo: Option<BeatFifo>,
+        audio: Option<AudioLevel>,
+        mut options: RunOptions,
+    ) -> Result<Self> {
+        self.tick_rate = tick_rate;
+        self.quiet = options.quiet;
+        let tick_rate = Duration::from_millis(tick_rate);
         let mut last_tick = Instant::now();
-        let mut i: u32 = 1;
+        let mut backoff = Backoff::new(options.adaptive);
 
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        while !self.exit && !options.turn_over() {
+            options.draw(&mut terminal, &mut backoff, |frame| {
+                self.draw(frame, options.glyphs())
+            })?;
+            let timeout = backoff
+                .timeout(tick_rate)
+                .saturating_sub(last_tick.elapsed())
+                .max(options.sleep_floor());
             if event::poll(timeout)? {
+                backoff.reset();
                 match event::read()? {
-                    Event::Key(key) => self.handle_key_press(key),
+                    Event::Key(key) if !options.skip_turn(key) => self.handle_key_press(key),
                     Event::Resize(_columns, _rows) => {
                         // self.debug_text = format!("{} {}", columns, rows);
                     }
@@ -79,82 +138,246 @@ impl App {
             }
 
             if last_tick.elapsed() >= tick_rate {
+                let mut secs = match &beat_fifo {
+                    Some(beat_fifo) => beat_fifo.take() as f64 * TICK_SECS,
+                    None => last_tick.elapsed().as_secs_f64(),
+                };
+                if let Some(audio) = &audio {
+                    self.color_boost = audio.boost(AudioParam::Colors);
+                    secs *= audio.boost(AudioParam::Rotation);
+                }
                 last_tick = Instant::now();
-                self.on_tick(i);
+                self.step(secs);
             }
-            i += 1;
         }
-        Ok(())
+        Ok(self)
+    }
+
+    /// command line reproducing the current settings, for --print-config-on-exit
+    pub fn to_cli_args(&self) -> String {
+        let mut args = format!(
+            "tunnel --marker {} --tick-rate {} --n-colors {} --speed {} --depth {} --mirror {}",
+            self.marker,
+            self.tick_rate,
+            self.n_colors,
+            self.rotation_speed,
+            self.depth,
+            value_enum_name(self.mirror)
+        );
+        if self.twist {
+            args += " --twist";
+        }
+        if self.ascii {
+            args += " --ascii";
+        }
+        if self.persistence > 0.0 {
+            args += &format!(" --persistence {}", self.persistence);
+        }
+        if self.smooth {
+            args += " --smooth";
+        }
+        args
     }
 
     fn handle_key_press(&mut self, key: event::KeyEvent) {
         if key.kind != KeyEventKind::Press {
             return;
         }
-        if is_quit_key(key) {
-            self.exit = true;
+        match key.code {
+            KeyCode::Char('c') if !is_quit_key(key) => self.show_palette = !self.show_palette,
+            _ => {
+                if is_quit_key(key) {
+                    self.exit = true;
+                }
+            }
         }
     }
 
-    fn on_tick(&mut self, i: u32) {
+    /// advance the animation by `secs` seconds
+    fn step(&mut self, secs: f64) {
+        self.elapsed_secs += secs;
+        self.on_tick();
+    }
+
+    fn on_tick(&mut self) {
         if self.grid.is_empty() {
             return;
         }
-        let i = i as f64 * 0.03 * self.rotation_speed;
+        let i = self.elapsed_secs * 3.75 * self.rotation_speed;
+        let n_colors = self.n_colors();
         let height = self.grid.len();
         let width = self.grid[0].len();
         let mid_y = height / 2;
         let mid_x = width / 2;
-        for y in 0..height {
-            for x in 0..width {
+        // only compute the part that isn't mirrored.
+        // rounding up keeps the middle row/column of odd sizes,
+        // so the copies line up exactly with the center and leave no seam
+        let computed_width = if self.mirror.horizontal() {
+            width.div_ceil(2)
+        } else {
+            width
+        };
+        let computed_height = if self.mirror.vertical() {
+            height.div_ceil(2)
+        } else {
+            height
+        };
+        for y in 0..computed_height {
+            for x in 0..computed_width {
                 let x2 = x as f64 - mid_x as f64;
                 let y2 = y as f64 - mid_y as f64;
                 let angle = y2.atan2(x2);
-                let mut a = (PI + angle) * self.n_colors as f64 / (2.0 * PI);
+                let mut a = (PI + angle) * n_colors as f64 / (2.0 * PI);
 
                 let r = match self.depth {
                     0 => 3.0 * i + (x2.powf(2.0) + y2.powf(2.0)).sqrt(),
                     1 => 3.0 * i + 20.0 * ((x2.powf(2.0) + y2.powf(2.0)).sqrt()).log2(),
-                    _ => 3.0 * i + 500.0 / (x2.powf(2.0) + y2.powf(2.0)).sqrt(),
+                    2 => 3.0 * i + 500.0 / (x2.powf(2.0) + y2.powf(2.0)).sqrt(),
+                    _ => unreachable!("depth is clamped to MAX_DEPTH"),
                 };
 
                 if self.twist {
                     let angle = angle + 0.05 * r;
-                    a = (PI + angle) * self.n_colors as f64 / (2.0 * PI);
+                    a = (PI + angle) * n_colors as f64 / (2.0 * PI);
                 }
 
                 let a2 = a + i;
-                let c = (a2 as u32 - (r * 0.10) as u32) % self.n_colors as u32;
-                self.grid[y][x] = c as u8;
+                self.grid[y][x] = if self.smooth && self.truecolor {
+                    (a2 - r * 0.10).rem_euclid(n_colors as f64)
+                } else {
+                    ((a2 as u32).wrapping_sub((r * 0.10) as u32) % n_colors as u32) as f64
+                };
+            }
+        }
+
+        if self.mirror.horizontal() {
+            for line in self.grid.iter_mut() {
+                for x in 0..(width / 2) {
+                    line[width - 1 - x] = line[x];
+                }
+            }
+        }
+        if self.mirror.vertical() {
+            for y in 0..(height / 2) {
+                self.grid[height - 1 - y] = self.grid[y].clone();
             }
         }
+        if let Some(afterglow) = &mut self.afterglow {
+            afterglow.blend(|x, y| Some(self.grid[y][x] as u8));
+        }
+    }
+
+    fn n_colors(&self) -> u8 {
+        (self.n_colors as f64 * self.color_boost).min(u8::MAX as f64) as u8
+    }
+
+    /// jump to the frame at `tick`
+    pub fn set_tick(&mut self, tick: u64) {
+        self.elapsed_secs = tick as f64 * TICK_SECS;
+        self.on_tick();
+    }
+
+    /// the current frame as a PPM image, one block per board cell
+    pub fn render_to_ppm(&self) -> Vec<u8> {
+        // image rows go down, canvas y goes up
+        let rows: Vec<Vec<u8>> = self
+            .grid
+            .iter()
+            .rev()
+            .map(|line| line.iter().map(|&color| color as u8).collect())
+            .collect();
+        encode_ppm(&rows, PPM_BLOCK_SIZE)
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self.canvas(), frame.area());
-        if !self.debug_text.is_empty() {
+    fn draw(&self, frame: &mut Frame, glyphs: &GlyphSet) {
+        if self.ascii {
+            let area = frame.area();
+            let (width, height) = (self.playground.x as usize, self.playground.y as usize);
+            let max = self.n_colors().saturating_sub(1).max(1) as f64;
+            draw_ascii(frame.buffer_mut(), area, glyphs, width, height, |x, y| {
+                self.grid[y][x] / max
+            });
+        } else {
+            frame.render_widget(self.canvas(), frame.area());
+        }
+        if self.show_palette && !self.quiet {
+            let area = frame.area();
+            draw_palette_swatch(frame.buffer_mut(), area, 0..self.n_colors());
+        }
+        if !self.quiet && !self.debug_text.is_empty() {
             let debug_text = Paragraph::new(self.debug_text.clone());
             frame.render_widget(debug_text, frame.area());
         }
     }
 
     fn canvas(&self) -> impl Widget + '_ {
+        let n_colors = self.n_colors();
+        // the bands in palette order, for shading between them
+        let stops: Vec<(u8, u8, u8)> = (0..n_colors).map(indexed_to_rgb).collect();
+        let smooth = self.smooth && self.truecolor;
         Canvas::default()
             .marker(self.marker)
-            .paint(|ctx| {
+            .paint(move |ctx| {
                 if self.grid.is_empty() {
                     return;
                 }
                 for (y, line) in self.grid.iter().enumerate() {
-                    for (x, color) in line.iter().enumerate() {
+                    for (x, &position) in line.iter().enumerate() {
+                        let color = match &self.afterglow {
+                            Some(afterglow) => Color::Indexed(afterglow.color(x, y).unwrap_or(0)),
+                            None if smooth => {
+                                let (r, g, b) = sample_gradient(&stops, position / n_colors as f64);
+                                Color::Rgb(r, g, b)
+                            }
+                            None => Color::Indexed(position as u8),
+                        };
                         ctx.draw(&Points {
                             coords: &[(x as f64, y as f64)],
-                            color: Color::Indexed(*color),
+                            color,
                         });
                     }
                 }
             })
-            .x_bounds([0.0, self.playground.x])
-            .y_bounds([0.0, self.playground.y])
+            // the canvas maps the bounds onto pixels 0..=resolution-1, so ending the bounds at
+            // the last cell puts every cell on its own pixel. with HalfBlock this keeps the
+            // upper and lower half of a character on separate rows instead of merging them
+            .x_bounds([0.0, self.playground.x - 1.0])
+            .y_bounds([0.0, self.playground.y - 1.0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    #[test]
+    fn frames_match_snapshots() {
+        for (depth, n_colors, ascii) in [(0, 4, false), (1, 16, false), (2, 8, false), (1, 8, true)]
+        {
+            let mut app = App::new(
+                24,
+                8,
+                Marker::HalfBlock,
+                n_colors,
+                1.0,
+                depth,
+                false,
+                Mirror::None,
+                ascii,
+                0.0,
+                false,
+            );
+            for _ in 0..10 {
+                app.step(TICK_SECS);
+            }
+            let mut terminal = Terminal::new(TestBackend::new(24, 8)).unwrap();
+            terminal
+                .draw(|frame| app.draw(frame, &GlyphSet::Ascii))
+                .unwrap();
+            let name = format!("tunnel_depth{depth}_colors{n_colors}_ascii{ascii}");
+            snapshot::assert_snapshot(&name, &snapshot::buffer_text(terminal.backend().buffer()));
+        }
     }
 }
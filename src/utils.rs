@@ -1,6 +1,1026 @@
-use crossterm::event::{self, KeyCode, KeyModifiers};
-use ratatui::symbols::Marker;
+use crate::glyphs::GlyphSet;
+use crate::palette::Palette;
+use clap::{Args, ValueEnum};
+use crossterm::event::{self, KeyCode, KeyEventKind, KeyModifiers};
+use glam::{DVec2, DVec3};
+use oorandom::Rand64;
+use ratatui::{
+    backend::Backend,
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    symbols::Marker,
+    widgets::canvas::{Context, Line},
+    Frame, Terminal,
+};
+use std::cell::Cell;
+use std::fs::File;
 use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// --background-char, dim enough to stay behind everything else
+const BACKGROUND_CHAR_COLOR: Color = Color::Indexed(238);
+
+/// Options shared by every toy
+#[derive(Args, Clone, Debug, Default)]
+pub struct RunOptions {
+    /// Slow down redraws while the picture isn't changing
+    #[arg(long, global = true, default_value_t = false)]
+    pub adaptive: bool,
+
+    /// Brighten or darken colors that are hard to see on the terminal background
+    #[arg(long, global = true, default_value_t = false)]
+    pub contrast: bool,
+
+    /// Never draw text overlays, for clean recordings
+    #[arg(long, global = true, default_value_t = false)]
+    pub quiet: bool,
+
+    /// Start every automatic reset from a new seed drawn from the previous one.
+    /// The sequence of scenes is still the same for a given --seed
+    #[arg(long, global = true, default_value_t = false)]
+    pub loop_seed: bool,
+
+    /// Draw everything in shades of gray
+    #[arg(long, global = true, default_value_t = false)]
+    pub mono: bool,
+
+    /// Wait at least this many milliseconds between frames to save power, even when a toy
+    /// falls behind its tick rate. Slow or heavy scenes get choppier in exchange
+    #[arg(long, global = true, value_name = "MILLISECONDS", default_value_t = 0)]
+    pub min_sleep: u64,
+
+    /// Replace the basic colors with a color-blind friendly set. Recolors the toys that
+    /// count through several colors (tunnel, bubble, sand, splits, life, ant)
+    #[arg(long, global = true, value_name = "PALETTE", value_enum, default_value_t = Palette::Default)]
+    pub palette: Palette,
+
+    /// Fill empty space with this character in a dim color, e.g. '.' for a faint dot grid,
+    /// for terminals that draw the background unevenly
+    #[arg(long, global = true, value_name = "CHAR")]
+    pub background_char: Option<char>,
+
+    /// Characters for the --ascii modes: ascii, katakana, binary, braille, or your own
+    /// from empty to full, e.g. " .oO@"
+    #[arg(long, global = true, value_name = "CHARSET")]
+    pub charset: Option<GlyphSet>,
+
+    /// terminal background, found by detect_background()
+    #[arg(skip)]
+    pub background: Option<Color>,
+
+    /// end of the current toy's turn with `cycle`
+    #[arg(skip)]
+    pub deadline: Option<Instant>,
+
+    /// where --dump-frames writes to
+    #[arg(skip)]
+    pub frame_dumper: Option<FrameDumper>,
+}
+
+/// writes the characters of drawn frames for --dump-frames, up to --max-frames of them.
+/// Clones share the count, so every toy of `cycle` numbers on from the last one instead of
+/// overwriting its frames
+#[derive(Clone, Debug)]
+pub struct FrameDumper {
+    dir: PathBuf,
+    max_frames: u64,
+    dumped: Rc<Cell<u64>>,
+}
+
+impl FrameDumper {
+    pub fn new(dir: PathBuf, max_frames: u64) -> Self {
+        Self {
+            dir,
+            max_frames,
+            dumped: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// frames written so far
+    pub fn count(&self) -> u64 {
+        self.dumped.get()
+    }
+
+    fn dump(&self, buffer: &Buffer) -> io::Result<()> {
+        let n = self.dumped.get();
+        if n >= self.max_frames {
+            return Ok(());
+        }
+        let mut text = String::new();
+        for line in buffer.content.chunks(buffer.area.width.max(1) as usize) {
+            for cell in line {
+                text += cell.symbol();
+            }
+            text.push('\n');
+        }
+        std::fs::write(frame_path(&self.dir, n + 1), text)?;
+        self.dumped.set(n + 1);
+        Ok(())
+    }
+
+    /// append the frames dumped so far in reverse for --ping-pong, leaving out the last
+    /// and the first one so the sequence loops without showing either twice in a row.
+    /// Frames an earlier, longer run left behind after the end are removed
+    pub fn ping_pong(&self) -> io::Result<()> {
+        let dir = &self.dir;
+        let n = self.count();
+        for i in (2..n).rev() {
+            std::fs::copy(frame_path(dir, i), frame_path(dir, 2 * n - i))?;
+        }
+        let end = (2 * n).saturating_sub(2).max(n);
+        for i in (end + 1..).take_while(|&i| frame_path(dir, i).exists()) {
+            std::fs::remove_file(frame_path(dir, i))?;
+        }
+        Ok(())
+    }
+}
+
+/// `s` in single quotes for pasting into a shell, a quote inside becomes '\''
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn frame_path(dir: &Path, n: u64) -> PathBuf {
+    dir.join(format!("frame_{n:06}.txt"))
+}
+
+// set when n ends a turn early with `cycle`, so main can tell skipping from quitting
+static TURN_SKIPPED: AtomicBool = AtomicBool::new(false);
+
+impl RunOptions {
+    /// ask the terminal for its background if --contrast is set, call in raw mode
+    pub fn detect_background(&mut self) {
+        if self.contrast {
+            self.background = query_background();
+        }
+    }
+
+    /// global flags for --print-config-on-exit, with a trailing space if not empty
+    pub fn to_cli_args(&self) -> String {
+        let mut args = String::new();
+        if self.adaptive {
+            args += "--adaptive ";
+        }
+        if self.contrast {
+            args += "--contrast ";
+        }
+        if self.quiet {
+            args += "--quiet ";
+        }
+        if self.loop_seed {
+            args += "--loop-seed ";
+        }
+        if self.mono {
+            args += "--mono ";
+        }
+        if self.min_sleep > 0 {
+            args += &format!("--min-sleep {} ", self.min_sleep);
+        }
+        if self.palette != Palette::Default {
+            args += &format!("--palette {} ", value_enum_name(self.palette));
+        }
+        if let Some(c) = self.background_char {
+            args += &format!("--background-char {} ", shell_quote(&c.to_string()));
+        }
+        if let Some(glyphs) = &self.charset {
+            args += &format!("--charset {} ", shell_quote(&glyphs.to_string()));
+        }
+        args
+    }
+
+    /// characters for the --ascii modes
+    pub fn glyphs(&self) -> &GlyphSet {
+        static ASCII: GlyphSet = GlyphSet::Ascii;
+        self.charset.as_ref().unwrap_or(&ASCII)
+    }
+
+    /// shortest wait for input between frames, for --min-sleep
+    pub fn sleep_floor(&self) -> Duration {
+        Duration::from_millis(self.min_sleep)
+    }
+
+    /// whether the toy should make way for the next one with `cycle`
+    pub fn turn_over(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// end the turn early if `key` is n with `cycle`, false if the toy should handle it
+    pub fn skip_turn(&mut self, key: event::KeyEvent) -> bool {
+        if self.deadline.is_none()
+            || key.kind != KeyEventKind::Press
+            || key.code != KeyCode::Char('n')
+        {
+            return false;
+        }
+        self.deadline = Some(Instant::now());
+        TURN_SKIPPED.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// whether the last turn was skipped with n, clears the flag
+    pub fn take_turn_skipped() -> bool {
+        TURN_SKIPPED.swap(false, Ordering::Relaxed)
+    }
+
+    /// draw one frame of a toy's run loop: `render` it, apply the color filters, let
+    /// `backoff` see it and write it for --dump-frames
+    pub fn draw<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        backoff: &mut Backoff,
+        render: impl FnOnce(&mut Frame),
+    ) -> color_eyre::Result<()>
+    where
+        B::Error: Send + Sync + 'static,
+    {
+        self.draw_recording(terminal, backoff, true, render)
+    }
+
+    /// like [`RunOptions::draw`], but the frame only goes to --dump-frames if `record`.
+    /// Paused toys pass false so the dump doesn't fill up with copies of the same frame
+    pub fn draw_recording<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        backoff: &mut Backoff,
+        record: bool,
+        render: impl FnOnce(&mut Frame),
+    ) -> color_eyre::Result<()>
+    where
+        B::Error: Send + Sync + 'static,
+    {
+        let frame = terminal.draw(|frame| {
+            render(frame);
+            self.apply_color_filters(frame.buffer_mut());
+        })?;
+        backoff.update(frame.buffer);
+        if let Some(dumper) = self.frame_dumper.as_ref().filter(|_| record) {
+            dumper.dump(frame.buffer)?;
+        }
+        Ok(())
+    }
+
+    /// fix up a drawn frame for --background-char, then every color for --palette, --mono
+    /// and --contrast so the backdrop gets the same treatment
+    pub fn apply_color_filters(&self, buffer: &mut Buffer) {
+        if let Some(c) = self.background_char {
+            let mut symbol = [0; 4];
+            let symbol = c.encode_utf8(&mut symbol);
+            for cell in buffer.content.iter_mut() {
+                // untouched cells only, anything with a color was drawn on purpose
+                if cell.symbol() == " " && cell.bg == Color::Reset {
+                    cell.set_symbol(symbol).set_fg(BACKGROUND_CHAR_COLOR);
+                }
+            }
+        }
+        if self.palette != Palette::Default {
+            for cell in buffer.content.iter_mut() {
+                cell.fg = self.palette.apply(cell.fg);
+                cell.bg = self.palette.apply(cell.bg);
+            }
+        }
+        if self.mono {
+            for cell in buffer.content.iter_mut() {
+                cell.fg = to_grayscale(cell.fg);
+                cell.bg = to_grayscale(cell.bg);
+            }
+        }
+        // contrast does nothing without a known background
+        let Some(bg) = self.background else {
+            return;
+        };
+        for cell in buffer.content.iter_mut() {
+            cell.fg = ensure_contrast(cell.fg, bg);
+            cell.bg = ensure_contrast(cell.bg, bg);
+        }
+    }
+}
+
+/// background color via OSC 11, None if the terminal doesn't answer in time
+#[cfg(unix)]
+fn query_background() -> Option<Color> {
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x1b]11;?\x1b\\").ok()?;
+    stdout.flush().ok()?;
+
+    let fd = io::stdin().as_raw_fd();
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let mut response = Vec::new();
+    // read byte by byte so nothing after the answer is taken away from crossterm
+    while response.len() < 64 {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: a single valid pollfd
+        if unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as i32) } <= 0 {
+            break;
+        }
+        let mut byte = 0u8;
+        // SAFETY: reading one byte into a local
+        if unsafe { libc::read(fd, (&mut byte as *mut u8).cast(), 1) } != 1 {
+            break;
+        }
+        response.push(byte);
+        // terminated by BEL or ST
+        if byte == 0x07 || response.ends_with(b"\x1b\\") {
+            break;
+        }
+    }
+    parse_osc_color(&response)
+}
+
+#[cfg(not(unix))]
+fn query_background() -> Option<Color> {
+    None
+}
+
+/// parse the rgb:RRRR/GGGG/BBBB part of an OSC color answer
+fn parse_osc_color(response: &[u8]) -> Option<Color> {
+    let response = std::str::from_utf8(response).ok()?;
+    let rgb = &response[response.find("rgb:")? + 4..];
+    let rgb = rgb.trim_end_matches(['\x07', '\x1b', '\\']);
+    let mut components = rgb.split('/').map(|hex| {
+        let hex = &hex[..hex.len().min(4)];
+        let max = 16u32.checked_pow(hex.len() as u32)? - 1;
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        Some((value * 255 / max) as u8)
+    });
+    Some(Color::Rgb(
+        components.next()??,
+        components.next()??,
+        components.next()??,
+    ))
+}
+
+/// palette index of a non rgb color, named colors are the first 16
+pub fn color_index(color: Color) -> Option<u8> {
+    let index = match color {
+        Color::Indexed(index) => index,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+        Color::Rgb(..) | Color::Reset => return None,
+    };
+    Some(index)
+}
+
+fn color_to_rgb(color: Color) -> Option<(u8, u8, u8)> {
+    match color {
+        Color::Rgb(r, g, b) => Some((r, g, b)),
+        _ => color_index(color).map(indexed_to_rgb),
+    }
+}
+
+fn luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) / 255.0
+}
+
+/// gray of the same luminance from the grayscale ramp (232..=255), keeps the default color
+pub fn to_grayscale(color: Color) -> Color {
+    match color_to_rgb(color) {
+        Some(rgb) => Color::Indexed(232 + (luminance(rgb) * 23.0).round() as u8),
+        None => color,
+    }
+}
+
+// smallest luminance difference to the background that still reads well
+const MIN_CONTRAST: f64 = 0.2;
+
+/// nudge `color` away from the background `bg` when they're hard to tell apart
+pub fn ensure_contrast(color: Color, bg: Color) -> Color {
+    let (Some(rgb), Some(bg_rgb)) = (color_to_rgb(color), color_to_rgb(bg)) else {
+        return color;
+    };
+    let bg_luminance = luminance(bg_rgb);
+    let readable = |rgb| (luminance(rgb) - bg_luminance).abs() >= MIN_CONTRAST;
+    if readable(rgb) {
+        return color;
+    }
+    let dark_bg = bg_luminance < 0.5;
+
+    let nudged = match color_index(color) {
+        None => {
+            let target = if dark_bg { 255.0 } else { 0.0 };
+            let blend = |v: u8| (v as f64 + (target - v as f64) * 0.6) as u8;
+            Color::Rgb(blend(rgb.0), blend(rgb.1), blend(rgb.2))
+        }
+        Some(index) => {
+            Color::Indexed(match index {
+                // swap between normal and bright
+                0..8 if dark_bg => index + 8,
+                8..16 if !dark_bg => index - 8,
+                0..16 => index,
+                // move each level of the color cube two steps
+                16..232 => {
+                    let i = index - 16;
+                    let shift = |v: u8| {
+                        if dark_bg {
+                            (v + 2).min(5)
+                        } else {
+                            v.saturating_sub(2)
+                        }
+                    };
+                    16 + 36 * shift(i / 36) + 6 * shift((i / 6) % 6) + shift(i % 6)
+                }
+                _ => {
+                    if dark_bg {
+                        index.saturating_add(12)
+                    } else {
+                        (index - 12).max(232)
+                    }
+                }
+            })
+        }
+    };
+
+    if readable(color_to_rgb(nudged).unwrap_or(rgb)) {
+        nudged
+    } else if dark_bg {
+        Color::Indexed(15)
+    } else {
+        Color::Indexed(0)
+    }
+}
+
+const MAX_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Stretches the poll timeout while consecutive frames are identical.
+/// Doubles every unchanged frame up to 250ms, any change or event resets it.
+pub struct Backoff {
+    enabled: bool,
+    last_hash: u64,
+    unchanged_frames: u32,
+}
+
+impl Backoff {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            last_hash: 0,
+            unchanged_frames: 0,
+        }
+    }
+
+    /// call with the buffer of every completed frame
+    pub fn update(&mut self, buffer: &Buffer) {
+        if !self.enabled {
+            return;
+        }
+        let hash = calculate_hash(buffer);
+        if hash == self.last_hash {
+            self.unchanged_frames = self.unchanged_frames.saturating_add(1);
+        } else {
+            self.unchanged_frames = 0;
+        }
+        self.last_hash = hash;
+    }
+
+    pub fn reset(&mut self) {
+        self.unchanged_frames = 0;
+    }
+
+    pub fn timeout(&self, tick_rate: Duration) -> Duration {
+        if self.unchanged_frames == 0 {
+            return tick_rate;
+        }
+        let factor = 1 << self.unchanged_frames.min(16);
+        // saturating, a huge --tick-rate times the factor overflows Duration
+        tick_rate
+            .saturating_mul(factor)
+            .min(MAX_BACKOFF.max(tick_rate))
+    }
+}
+
+/// Counts bytes written to a named pipe by another process, one beat per byte.
+/// Reading happens on a background thread so the render loop never blocks.
+pub struct BeatFifo {
+    beats: Arc<AtomicU64>,
+}
+
+impl BeatFifo {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if !std::fs::metadata(path)?.file_type().is_fifo() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a named pipe", path.display()),
+                ));
+            }
+        }
+        #[cfg(not(unix))]
+        std::fs::metadata(path)?;
+
+        let beats = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&beats);
+        let path = path.to_path_buf();
+        std::thread::spawn(move || {
+            let mut buf = [0; 256];
+            // opening blocks until a writer shows up, reopen whenever the writer goes away
+            while let Ok(mut file) = File::open(&path) {
+                while let Ok(n) = file.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                    counter.fetch_add(n as u64, Ordering::Relaxed);
+                }
+            }
+        });
+        Ok(Self { beats })
+    }
+
+    /// beats received since the last call
+    pub fn take(&self) -> u64 {
+        self.beats.swap(0, Ordering::Relaxed)
+    }
+}
+
+// music rarely gets anywhere near full scale, so a quiet room leaves a setting
+// as it is and a loud song scales it up a few times
+const AUDIO_GAIN: f64 = 4.0;
+
+/// Setting that --audio-reactive drives from the input level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum AudioParam {
+    /// Animation speed (tunnel, bubble, cube)
+    #[default]
+    Rotation,
+    /// Size of the bubble, height of the cube's wave (bubble, cube)
+    Amplitude,
+    /// Number of colors (tunnel, bubble)
+    Colors,
+}
+
+/// Loudness of the default audio input device, a loopback/monitor device picks up
+/// whatever the system is playing. The audio backend's callback thread keeps it up to date.
+pub struct AudioLevel {
+    // f32 bits of the RMS of the latest buffer
+    rms: Arc<AtomicU32>,
+    pub param: AudioParam,
+    #[cfg(feature = "audio")]
+    _stream: cpal::Stream,
+}
+
+impl AudioLevel {
+    #[cfg(feature = "audio")]
+    pub fn open(param: AudioParam) -> color_eyre::Result<Self> {
+        use color_eyre::eyre::eyre;
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+        use cpal::SampleFormat;
+
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| eyre!("no audio input device found for --audio-reactive"))?;
+        let supported = device.default_input_config()?;
+        let rms = Arc::new(AtomicU32::new(0));
+        let config = supported.config();
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => rms_stream::<f32>(&device, config, &rms)?,
+            SampleFormat::I16 => rms_stream::<i16>(&device, config, &rms)?,
+            SampleFormat::U16 => rms_stream::<u16>(&device, config, &rms)?,
+            SampleFormat::I32 => rms_stream::<i32>(&device, config, &rms)?,
+            format => return Err(eyre!("unsupported audio sample format {format:?}")),
+        };
+        stream.play()?;
+        Ok(Self {
+            rms,
+            param,
+            _stream: stream,
+        })
+    }
+
+    #[cfg(not(feature = "audio"))]
+    pub fn open(_param: AudioParam) -> color_eyre::Result<Self> {
+        Err(color_eyre::eyre::eyre!(
+            "--audio-reactive needs terminal-toys built with `--features audio`"
+        ))
+    }
+
+    /// current level, 0 is silence and 1 full scale
+    pub fn level(&self) -> f64 {
+        (f32::from_bits(self.rms.load(Ordering::Relaxed)) as f64).clamp(0.0, 1.0)
+    }
+
+    /// factor to scale `param` by, 1 when another setting is driven
+    pub fn boost(&self, param: AudioParam) -> f64 {
+        if self.param == param {
+            1.0 + AUDIO_GAIN * self.level()
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+fn rms_stream<T>(
+    device: &cpal::Device,
+    config: cpal::StreamConfig,
+    rms: &Arc<AtomicU32>,
+) -> color_eyre::Result<cpal::Stream>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    use cpal::traits::DeviceTrait;
+
+    let rms = Arc::clone(rms);
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            if data.is_empty() {
+                return;
+            }
+            let sum: f32 = data
+                .iter()
+                .map(|sample| sample.to_sample::<f32>().powi(2))
+                .sum();
+            let level = (sum / data.len() as f32).sqrt();
+            rms.store(level.to_bits(), Ordering::Relaxed);
+        },
+        // on stream errors the level stays where it was
+        |_| {},
+        None,
+    )?;
+    Ok(stream)
+}
+
+// perspective divisor limits, 0 divides by zero and negative values flip the image
+pub const FOV_MIN: f64 = 0.001;
+pub const FOV_MAX: f64 = 0.1;
+
+pub trait ToScreenPos {
+    fn to_screen_position(self, playground: Rect, val: f64) -> DVec2;
+    fn to_screen_position_orthographic(self, playground: Rect, scale: f64) -> DVec2;
+}
+
+impl ToScreenPos for DVec3 {
+    fn to_screen_position(self, playground: Rect, val: f64) -> DVec2 {
+        let z = self.z;
+        let x = (self.x) / (val * z);
+        let y = (self.y) / (val * z);
+
+        DVec2 {
+            x: x + playground.right() as f64 * 0.5,
+            y: y + playground.bottom() as f64 * 0.5,
+        }
+    }
+
+    fn to_screen_position_orthographic(self, playground: Rect, scale: f64) -> DVec2 {
+        let x = scale * self.x + 0.4 * self.z * scale;
+        let y = scale * self.y + 0.4 * self.z * scale;
+
+        DVec2 {
+            x: x + playground.right() as f64 * 0.5,
+            y: y + playground.bottom() as f64 * 0.5,
+        }
+    }
+}
+
+/// grayscale that gets brighter the closer depth is to near
+pub fn depth_color(depth: f64, near: f64, far: f64) -> Color {
+    let brightness = map_range(depth, far, near, 0.0, 1.0).clamp(0.0, 1.0);
+    // 232..=255 is the grayscale ramp, skip the darkest ones
+    Color::Indexed(236 + (brightness * 19.0).round() as u8)
+}
+
+/// pixels per board cell in rendered images
+pub const PPM_BLOCK_SIZE: usize = 4;
+
+/// RGB value of a 256 color palette index (xterm defaults)
+pub fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    match index {
+        0..16 => ANSI[index as usize],
+        16..232 => {
+            let i = index - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            (level(i / 36), level((i / 6) % 6), level(i % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// color at `t` along a looping gradient through `stops`, 0 and 1 are both the first stop
+pub fn sample_gradient(stops: &[(u8, u8, u8)], t: f64) -> (u8, u8, u8) {
+    if stops.is_empty() {
+        return (0, 0, 0);
+    }
+    let position = t.rem_euclid(1.0) * stops.len() as f64;
+    let i = (position as usize).min(stops.len() - 1);
+    let (from, to) = (stops[i], stops[(i + 1) % stops.len()]);
+    let fraction = position - i as f64;
+    let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * fraction).round() as u8;
+    (lerp(from.0, to.0), lerp(from.1, to.1), lerp(from.2, to.2))
+}
+
+/// whether COLORTERM says the terminal takes 24 bit colors
+pub fn supports_truecolor() -> bool {
+    matches!(
+        std::env::var("COLORTERM").as_deref(),
+        Ok("truecolor" | "24bit")
+    )
+}
+
+/// closest color of the 6x6x6 cube (16..=231) to an RGB value
+pub fn rgb_to_indexed(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |v: u8| {
+        (0..6)
+            .min_by_key(|&i| (LEVELS[i] as i32 - v as i32).abs())
+            .unwrap() as u8
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+/// fading copy of earlier frames for --persistence, kept in rgb so any palette colors blend
+pub struct Afterglow {
+    persistence: f64,
+    rgb: Vec<Vec<[f64; 3]>>,
+}
+
+impl Afterglow {
+    pub fn new(persistence: f64, width: usize, height: usize) -> Self {
+        Self {
+            persistence,
+            rgb: vec![vec![[0.0; 3]; width]; height],
+        }
+    }
+
+    /// fade the earlier frames and mix in a new one, `color` is None where it's black
+    pub fn blend(&mut self, color: impl Fn(usize, usize) -> Option<u8>) {
+        for (y, line) in self.rgb.iter_mut().enumerate() {
+            for (x, cell) in line.iter_mut().enumerate() {
+                let (r, g, b) = color(x, y).map_or((0, 0, 0), indexed_to_rgb);
+                for (channel, new) in cell.iter_mut().zip([r, g, b]) {
+                    *channel = self.persistence * *channel + (1.0 - self.persistence) * new as f64;
+                }
+            }
+        }
+    }
+
+    /// palette index of the blended color at (x, y), None where it has faded out
+    pub fn color(&self, x: usize, y: usize) -> Option<u8> {
+        let [r, g, b] = self.rgb[y][x];
+        // below the darkest non-black level of the color cube
+        if r.max(g).max(b) < 48.0 {
+            return None;
+        }
+        Some(rgb_to_indexed(r as u8, g as u8, b as u8))
+    }
+}
+
+/// binary PPM (P6) of rows of palette indices, top row first,
+/// each cell becomes a `block_size` x `block_size` square
+pub fn encode_ppm(rows: &[Vec<u8>], block_size: usize) -> Vec<u8> {
+    let height = rows.len() * block_size;
+    let width = rows.first().map_or(0, |row| row.len()) * block_size;
+    let mut ppm = format!("P6\n{width} {height}\n255\n").into_bytes();
+    ppm.reserve(width * height * 3);
+    for row in rows {
+        for _ in 0..block_size {
+            for &index in row {
+                let (r, g, b) = indexed_to_rgb(index);
+                for _ in 0..block_size {
+                    ppm.extend_from_slice(&[r, g, b]);
+                }
+            }
+        }
+    }
+    ppm
+}
+
+/// draw a `width` x `height` board as `glyphs` ramp characters straight into the buffer,
+/// every character shows the average `level(x, y)` of the cells it covers.
+/// y = 0 is the bottom row like on the canvas
+pub fn draw_ascii(
+    buffer: &mut Buffer,
+    area: Rect,
+    glyphs: &GlyphSet,
+    width: usize,
+    height: usize,
+    level: impl Fn(usize, usize) -> f64,
+) {
+    if width == 0 || height == 0 || area.is_empty() {
+        return;
+    }
+    let (columns, rows) = (area.width as usize, area.height as usize);
+    for row in 0..rows {
+        // rows go down, board y goes up
+        let y_range = cell_range(rows - 1 - row, rows, height);
+        for col in 0..columns {
+            let x_range = cell_range(col, columns, width);
+            let n = (x_range.len() * y_range.len()) as f64;
+            let sum: f64 = y_range
+                .clone()
+                .flat_map(|y| x_range.clone().map(move |x| (x, y)))
+                .map(|(x, y)| level(x, y))
+                .sum();
+            let position = (area.x + col as u16, area.y + row as u16);
+            buffer[position].set_char(glyphs.intensity_to_char(sum / n));
+        }
+    }
+}
+
+// characters per color in the palette swatch
+const SWATCH_WIDTH: u16 = 4;
+
+/// numbered swatches of palette `colors` along the bottom of `area`, wrapping upwards
+pub fn draw_palette_swatch(buffer: &mut Buffer, area: Rect, colors: impl IntoIterator<Item = u8>) {
+    let per_row = (area.width / SWATCH_WIDTH).max(1) as usize;
+    let colors: Vec<u8> = colors.into_iter().collect();
+    let n_rows = colors.len().div_ceil(per_row);
+    for (i, color) in colors.into_iter().enumerate() {
+        let row = (n_rows - 1 - i / per_row) as u16;
+        let x = area.x + (i % per_row) as u16 * SWATCH_WIDTH;
+        let Some(y) = (area.y + area.height).checked_sub(1 + row) else {
+            continue;
+        };
+        if y < area.y || x + SWATCH_WIDTH > area.x + area.width {
+            continue;
+        }
+        // readable number on light and dark swatches
+        let text = if luminance(indexed_to_rgb(color)) < 0.5 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        buffer.set_string(
+            x,
+            y,
+            format!("{color:^width$}", width = SWATCH_WIDTH as usize),
+            Style::default().fg(text).bg(Color::Indexed(color)),
+        );
+    }
+}
+
+/// board cells covered by character `i` of `n`, at least one
+fn cell_range(i: usize, n: usize, cells: usize) -> std::ops::Range<usize> {
+    let start = (i * cells / n).min(cells - 1);
+    let end = ((i + 1) * cells / n).clamp(start + 1, cells);
+    start..end
+}
+
+/// order 3d segments farthest first (larger z is farther), so nearer ones are drawn on top
+pub fn sort_back_to_front<T>(segments: &mut [(DVec3, DVec3, T)]) {
+    segments.sort_by(|a, b| (b.0.z + b.1.z).total_cmp(&(a.0.z + a.1.z)));
+}
+
+/// fill a triangle with horizontal lines `step` canvas units apart
+pub fn fill_triangle(ctx: &mut Context, corners: [DVec2; 3], color: Color, step: f64) {
+    let mut corners = corners;
+    corners.sort_by(|a, b| a.y.total_cmp(&b.y));
+    let [a, b, c] = corners;
+    // x where the edge p -> q crosses height y
+    let edge_x = |p: DVec2, q: DVec2, y: f64| {
+        if (q.y - p.y).abs() < f64::EPSILON {
+            q.x
+        } else {
+            p.x + (q.x - p.x) * (y - p.y) / (q.y - p.y)
+        }
+    };
+    let mut y = a.y;
+    while y <= c.y {
+        let x0 = edge_x(a, c, y);
+        let x1 = if y < b.y {
+            edge_x(a, b, y)
+        } else {
+            edge_x(b, c, y)
+        };
+        ctx.draw(&Line::new(x0, y, x1, y, color));
+        y += step;
+    }
+}
+
+/// draw `thickness` parallel lines one canvas unit apart, centered on p0 -> p1
+pub fn draw_thick_line(ctx: &mut Context, p0: DVec2, p1: DVec2, color: Color, thickness: u8) {
+    let normal = (p1 - p0).perp().normalize_or_zero();
+    for i in 0..thickness {
+        let offset = normal * (i as f64 - (thickness - 1) as f64 / 2.0);
+        let (a, b) = (p0 + offset, p1 + offset);
+        ctx.draw(&Line::new(a.x, a.y, b.x, b.y, color));
+    }
+}
+
+/// a 3d frame after projection, in canvas coordinates. Built outside the canvas closure so
+/// --skip-unchanged can compare it with the last drawn one
+#[derive(Default)]
+pub struct Scene {
+    pub lines: Vec<(DVec2, DVec2, Color)>,
+    pub quads: Vec<([DVec2; 4], Color)>,
+}
+
+impl Scene {
+    /// draw the quads filled and the lines `thickness` wide
+    pub fn paint(&self, ctx: &mut Context, thickness: u8) {
+        // canvas units between the scanlines filling a quad
+        const FILL_STEP: f64 = 0.5;
+        for &([a, b, c, d], color) in &self.quads {
+            fill_triangle(ctx, [a, b, c], color, FILL_STEP);
+            fill_triangle(ctx, [a, c, d], color, FILL_STEP);
+        }
+        for &(p0, p1, color) in &self.lines {
+            draw_thick_line(ctx, p0, p1, color, thickness);
+        }
+    }
+
+    /// hash of the scene rounded to an eighth of a canvas unit, well below a braille dot,
+    /// so movement too small to show doesn't count as a change
+    pub fn hash(&self) -> u64 {
+        let round = |p: DVec2| ((p.x * 8.0).round() as i64, (p.y * 8.0).round() as i64);
+        let lines: Vec<_> = self
+            .lines
+            .iter()
+            .map(|&(p0, p1, color)| (round(p0), round(p1), color))
+            .collect();
+        let quads: Vec<_> = self
+            .quads
+            .iter()
+            .map(|&(corners, color)| (corners.map(round), color))
+            .collect();
+        calculate_hash(&(lines, quads))
+    }
+}
+
+/// parse a float that isn't NaN or infinite, those leave the 3d toys blank or frozen
+pub fn finite_f64(s: &str) -> Result<f64, String> {
+    let value: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{value} is not a finite number"))
+    }
+}
+
+/// parse a --seed: a number, or "random" for one taken from the clock. The toys only ever
+/// see the number, so --print-config-on-exit shows the seed that was picked
+pub fn seed_from_str(s: &str) -> Result<u128, String> {
+    if s == "random" {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        // nanoseconds still fit in a u64 for a few centuries, small enough for every toy
+        return Ok(now.as_nanos() as u64 as u128);
+    }
+    s.parse().map_err(|e| format!("{e}"))
+}
+
+/// seed for the next scene with --loop-seed
+pub fn next_seed(rng: &mut Rand64) -> u128 {
+    ((rng.rand_u64() as u128) << 64) | rng.rand_u64() as u128
+}
+
+/// name of a value enum as typed on the command line
+pub fn value_enum_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
 
 pub fn map_range(val: f64, in_min: f64, in_max: f64, out_min: f64, out_max: f64) -> f64 {
     (val - in_min) / (in_max - in_min) * (out_max - out_min) + out_min
@@ -12,12 +1032,68 @@ pub fn calculate_hash<T: Hash>(t: &T) -> u64 {
     s.finish()
 }
 
+/// What happens at the edges of a grid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Topology {
+    /// Wrap around to the opposite edge
+    #[default]
+    Torus,
+    /// Nothing exists past the edge
+    Bounded,
+    /// Reflect back into the grid
+    Mirror,
+}
+
+/// Reflects the picture across the center axes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Mirror {
+    #[default]
+    None,
+    /// Left half is mirrored onto the right half
+    Horizontal,
+    /// Bottom half is mirrored onto the top half
+    Vertical,
+    /// Both
+    Quad,
+}
+
+impl Mirror {
+    pub fn horizontal(self) -> bool {
+        matches!(self, Mirror::Horizontal | Mirror::Quad)
+    }
+
+    pub fn vertical(self) -> bool {
+        matches!(self, Mirror::Vertical | Mirror::Quad)
+    }
+}
+
+/// map a possibly out of range coordinate into 0..max
+/// returns None if it falls off a bounded grid
+pub fn wrap_coord(v: i32, max: i32, topo: Topology) -> Option<usize> {
+    if max <= 0 {
+        return None;
+    }
+    match topo {
+        Topology::Torus => Some(v.rem_euclid(max) as usize),
+        Topology::Bounded => (0..max).contains(&v).then_some(v as usize),
+        Topology::Mirror => {
+            let v = v.rem_euclid(2 * max);
+            if v < max {
+                Some(v as usize)
+            } else {
+                Some((2 * max - 1 - v) as usize)
+            }
+        }
+    }
+}
+
 pub fn is_quit_key(key: event::KeyEvent) -> bool {
     match key.code {
         KeyCode::Esc => true,
         KeyCode::Char('q') => true,
         KeyCode::Char('Q') => true,
-        KeyCode::Char('c') => key.modifiers == KeyModifiers::CONTROL,
+        // also with shift or alt held, ctrl-shift-c comes in as 'C'
+        KeyCode::Char('c') | KeyCode::Char('C') => key.modifiers.contains(KeyModifiers::CONTROL),
         _ => false,
     }
 }
@@ -62,3 +1138,89 @@ pub fn calc_board_size_scaled(
         _ => (terminal_width as usize, (terminal_height * 2) as usize),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyEvent;
+
+    #[test]
+    fn ctrl_c_quits_with_extra_modifiers() {
+        for modifiers in [
+            KeyModifiers::CONTROL,
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT | KeyModifiers::ALT,
+        ] {
+            for c in ['c', 'C'] {
+                assert!(is_quit_key(KeyEvent::new(KeyCode::Char(c), modifiers)));
+            }
+        }
+    }
+
+    #[test]
+    fn c_without_ctrl_does_not_quit() {
+        for modifiers in [KeyModifiers::NONE, KeyModifiers::SHIFT, KeyModifiers::ALT] {
+            for c in ['c', 'C'] {
+                assert!(!is_quit_key(KeyEvent::new(KeyCode::Char(c), modifiers)));
+            }
+        }
+    }
+
+    /// an empty directory for a test's frames
+    fn frame_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("terminal-toys-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn frame_dumper_counts_across_clones() {
+        let dir = frame_dir("dumper");
+        let dumper = FrameDumper::new(dir.clone(), 3);
+        let turn = dumper.clone();
+        let buffer = Buffer::empty(Rect::new(0, 0, 4, 2));
+        turn.dump(&buffer).unwrap();
+        turn.dump(&buffer).unwrap();
+        dumper.clone().dump(&buffer).unwrap();
+        // past --max-frames
+        dumper.dump(&buffer).unwrap();
+        assert_eq!(dumper.count(), 3);
+        assert!(frame_path(&dir, 3).exists());
+        assert!(!frame_path(&dir, 4).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn backoff_survives_huge_tick_rates() {
+        let mut backoff = Backoff::new(true);
+        backoff.unchanged_frames = 40;
+        assert_eq!(backoff.timeout(Duration::MAX), Duration::MAX);
+        let tick_rate = Duration::from_millis(u64::MAX);
+        assert_eq!(backoff.timeout(tick_rate), tick_rate);
+        assert_eq!(backoff.timeout(Duration::from_millis(10)), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn quotes_charset_for_the_shell() {
+        let options = RunOptions {
+            background_char: Some('\''),
+            charset: Some(" it's".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.to_cli_args(),
+            r"--background-char ''\''' --charset ' it'\''s' "
+        );
+    }
+
+    #[test]
+    fn parses_seeds() {
+        assert_eq!(seed_from_str("42"), Ok(42));
+        assert_eq!(seed_from_str("0"), Ok(0));
+        assert!(seed_from_str("random").is_ok_and(|seed| seed <= u64::MAX as u128));
+        assert!(seed_from_str("-1").is_err());
+        assert!(seed_from_str("Random").is_err());
+    }
+}